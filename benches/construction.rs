@@ -0,0 +1,40 @@
+//! Measures the time to construct `TextLines` for text with different
+//! amounts of non-ASCII characters.
+//!
+//! Run with `cargo bench --bench construction`. The fastest of several runs
+//! is reported for each text, which is about 13 MB of code with 191,177
+//! lines except for the minified text, which is a single 6 MB line.
+
+use std::time::Duration;
+use std::time::Instant;
+
+use text_lines::TextLines;
+
+const RUNS: usize = 10;
+
+fn main() {
+  let line = "  let value = compute(alpha, beta) + gamma; // é comment text here\n";
+  // one accented character per line
+  let mixed = line.repeat(13_000_000 / line.len());
+  let ascii = mixed.replace('é', "e");
+  // a single accented character at the end of a long line
+  let minified = format!("{}é", "a+b;".repeat(1_500_000));
+
+  for (name, text) in [
+    ("ascii", &ascii),
+    ("mixed", &mixed),
+    ("minified", &minified),
+  ] {
+    let fastest = (0..RUNS)
+      .map(|_| {
+        let start = Instant::now();
+        let lines = TextLines::new(text);
+        let elapsed = start.elapsed();
+        assert!(lines.lines_count() > 0);
+        elapsed
+      })
+      .min()
+      .unwrap_or(Duration::ZERO);
+    println!("{:<8} {:?}", name, fastest);
+  }
+}
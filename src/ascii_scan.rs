@@ -0,0 +1,101 @@
+use std::convert::TryInto;
+
+const WORD_SIZE: usize = std::mem::size_of::<usize>();
+/// `0x0101...01`
+const LOW_BITS: usize = usize::MAX / 0xFF;
+/// `0x8080...80`
+const HIGH_BITS: usize = LOW_BITS * 0x80;
+
+/// Gets the index of the first byte at or after `index` that needs to be
+/// looked at individually when scanning lines or the length of the bytes
+/// when there is none.
+///
+/// Runs of plain ASCII are skipped a word at a time. The bytes that need to
+/// be looked at are non-ASCII bytes, `\n`, `\r` and `\t` along with vertical
+/// tab and form feed when `include_vt_ff` is true.
+pub(crate) fn find_special_byte(bytes: &[u8], index: usize, include_vt_ff: bool) -> usize {
+  let mut index = index;
+  while let Some(word_bytes) = bytes.get(index..index + WORD_SIZE) {
+    let word = usize::from_ne_bytes(word_bytes.try_into().unwrap());
+    if has_special_byte(word, include_vt_ff) {
+      break;
+    }
+    index += WORD_SIZE;
+  }
+  while index < bytes.len() && !is_special_byte(bytes[index], include_vt_ff) {
+    index += 1;
+  }
+  index
+}
+
+fn is_special_byte(byte: u8, include_vt_ff: bool) -> bool {
+  !byte.is_ascii()
+    || matches!(byte, b'\n' | b'\r' | b'\t')
+    || include_vt_ff && matches!(byte, 0x0B | 0x0C)
+}
+
+fn has_special_byte(word: usize, include_vt_ff: bool) -> bool {
+  word & HIGH_BITS != 0
+    || has_byte(word, b'\n')
+    || has_byte(word, b'\r')
+    || has_byte(word, b'\t')
+    || include_vt_ff && (has_byte(word, 0x0B) || has_byte(word, 0x0C))
+}
+
+/// Gets if any byte in the word equals the provided byte.
+fn has_byte(word: usize, byte: u8) -> bool {
+  let word = word ^ (LOW_BITS * byte as usize);
+  // only has the high bit set for a byte when there is a zero byte
+  word.wrapping_sub(LOW_BITS) & !word & HIGH_BITS != 0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_special_bytes() {
+    let special_texts = ["\n", "\r", "\t", "β", "😀", "\u{0B}", "\u{0C}"];
+    // place each special text at every position across multiple words
+    for length in 0..WORD_SIZE * 3 {
+      for special_text in special_texts {
+        for position in 0..=length {
+          let text = format!(
+            "{}{}{}",
+            "a~".repeat(length).get(..position).unwrap(),
+            special_text,
+            "z".repeat(length - position)
+          );
+          for include_vt_ff in [false, true] {
+            for start_index in 0..=position {
+              assert_eq!(
+                find_special_byte(text.as_bytes(), start_index, include_vt_ff),
+                find_special_byte_naive(text.as_bytes(), start_index, include_vt_ff),
+                "Text: {:?}, Start index: {}, Include VT/FF: {}",
+                text,
+                start_index,
+                include_vt_ff,
+              );
+            }
+          }
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn finds_no_special_bytes() {
+    for length in 0..WORD_SIZE * 3 {
+      let text = " !~\u{7F}\u{00}".repeat(length);
+      assert_eq!(find_special_byte(text.as_bytes(), 0, true), text.len());
+    }
+  }
+
+  fn find_special_byte_naive(bytes: &[u8], index: usize, include_vt_ff: bool) -> usize {
+    bytes[index..]
+      .iter()
+      .position(|byte| is_special_byte(*byte, include_vt_ff))
+      .map(|position| index + position)
+      .unwrap_or(bytes.len())
+  }
+}
@@ -0,0 +1,170 @@
+use std::io;
+use std::io::Read;
+
+use crate::TextLines;
+use crate::TextLinesError;
+use crate::TextLinesOptions;
+
+/// Builds a `TextLines` from chunks of UTF-8 text without holding
+/// the entire text in memory.
+///
+/// A character or `\r\n` newline may be split across chunks.
+#[derive(Debug, Clone)]
+pub struct TextLinesBuilder {
+  lines: TextLines,
+  /// The bytes of a character that was split across chunks.
+  incomplete_char: Vec<u8>,
+}
+
+impl TextLinesBuilder {
+  /// Creates a new `TextLinesBuilder` with the specified options.
+  pub fn new(options: TextLinesOptions) -> Self {
+    TextLinesBuilder {
+      lines: TextLines::with_options("", options),
+      incomplete_char: Vec::new(),
+    }
+  }
+
+  /// Gets the number of bytes that have been pushed.
+  pub fn bytes_len(&self) -> usize {
+    self.lines.text_length() + self.incomplete_char.len()
+  }
+
+  /// Adds the next chunk of text.
+  pub fn push_str(&mut self, text: &str) -> Result<(), TextLinesError> {
+    self.push(text.as_bytes())
+  }
+
+  /// Adds the next chunk of bytes, which may start or end in the
+  /// middle of a character.
+  ///
+  /// An error is returned with the byte index of the first invalid
+  /// UTF-8 sequence, in which case the bytes before it are kept.
+  pub fn push(&mut self, bytes: &[u8]) -> Result<(), TextLinesError> {
+    let mut bytes = bytes;
+    // complete the character split from the previous chunk
+    while !self.incomplete_char.is_empty() && !bytes.is_empty() {
+      self.incomplete_char.push(bytes[0]);
+      bytes = &bytes[1..];
+      match std::str::from_utf8(&self.incomplete_char) {
+        Ok(text) => {
+          self.lines.append(text);
+          self.incomplete_char.clear();
+        }
+        Err(err) if err.error_len().is_some() => {
+          self.incomplete_char.clear();
+          return Err(TextLinesError::InvalidUtf8 {
+            byte_index: self.lines.text_length(),
+          });
+        }
+        Err(_) => {} // still incomplete
+      }
+    }
+
+    match std::str::from_utf8(bytes) {
+      Ok(text) => self.lines.append(text),
+      Err(err) => {
+        let (valid_bytes, invalid_bytes) = bytes.split_at(err.valid_up_to());
+        self.lines.append(std::str::from_utf8(valid_bytes).unwrap());
+        if err.error_len().is_some() {
+          return Err(TextLinesError::InvalidUtf8 {
+            byte_index: self.lines.text_length(),
+          });
+        }
+        self.incomplete_char.extend(invalid_bytes);
+      }
+    }
+    Ok(())
+  }
+
+  /// Adds all the bytes read from the reader.
+  pub fn read_from(&mut self, mut reader: impl Read) -> io::Result<()> {
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+      let len = match reader.read(&mut buffer) {
+        Ok(0) => return Ok(()),
+        Ok(len) => len,
+        Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+        Err(err) => return Err(err),
+      };
+      self
+        .push(&buffer[..len])
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    }
+  }
+
+  /// Gets the `TextLines` for the pushed text.
+  ///
+  /// An error is returned when the text ends in the middle of a character.
+  pub fn finish(self) -> Result<TextLines, TextLinesError> {
+    if self.incomplete_char.is_empty() {
+      Ok(self.lines)
+    } else {
+      Err(TextLinesError::InvalidUtf8 {
+        byte_index: self.lines.text_length(),
+      })
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_helpers::for_each_oracle;
+
+  #[test]
+  fn matches_text_lines() {
+    for_each_oracle(|text, options, expected| {
+      for chunk_size in 1..text.len() {
+        let mut builder = TextLinesBuilder::new(options);
+        for chunk in text.as_bytes().chunks(chunk_size) {
+          builder.push(chunk).unwrap();
+        }
+        assert_eq!(builder.bytes_len(), text.len());
+        assert_eq!(
+          &builder.finish().unwrap(),
+          expected,
+          "Text: {:?}, Chunk size: {}",
+          text,
+          chunk_size
+        );
+      }
+
+      let mut builder = TextLinesBuilder::new(options);
+      builder.read_from(text.as_bytes()).unwrap();
+      assert_eq!(&builder.finish().unwrap(), expected);
+    });
+  }
+
+  #[test]
+  fn invalid_utf8() {
+    let mut builder = TextLinesBuilder::new(Default::default());
+    builder.push(b"a\n\xCE").unwrap();
+    assert_eq!(
+      builder.push(b"b"),
+      Err(TextLinesError::InvalidUtf8 { byte_index: 2 })
+    );
+
+    let mut builder = TextLinesBuilder::new(Default::default());
+    assert_eq!(
+      builder.push(b"ab\xFFc"),
+      Err(TextLinesError::InvalidUtf8 { byte_index: 2 })
+    );
+    assert_eq!(builder.bytes_len(), 2);
+
+    let mut builder = TextLinesBuilder::new(Default::default());
+    builder.push(b"a\xF0\x9F").unwrap();
+    assert_eq!(
+      builder.finish(),
+      Err(TextLinesError::InvalidUtf8 { byte_index: 1 })
+    );
+
+    let mut builder = TextLinesBuilder::new(Default::default());
+    let err = builder.read_from(&b"a\n\x80"[..]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(
+      err.to_string(),
+      "The text was not valid UTF-8 at byte index 2."
+    );
+  }
+}
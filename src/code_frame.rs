@@ -0,0 +1,325 @@
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::unwrap_or_panic;
+use crate::ByteRange;
+use crate::LineSegment;
+use crate::TextLines;
+use crate::TextLinesError;
+
+const GUTTER_COLOR: &str = "\x1b[1;34m";
+const PRIMARY_COLOR: &str = "\x1b[1;31m";
+const SECONDARY_COLOR: &str = "\x1b[1;33m";
+const RESET_COLOR: &str = "\x1b[0m";
+
+/// How a label is underlined.
+#[cfg_attr(
+  feature = "serialization",
+  derive(serde::Serialize, serde::Deserialize)
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelStyle {
+  /// Underlined with carets (`^`).
+  Primary,
+  /// Underlined with tildes (`~`).
+  Secondary,
+}
+
+/// A byte range to underline in a code frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+  pub range: ByteRange,
+  /// The message shown after the underline on the last line of the range.
+  pub message: Option<String>,
+  pub style: LabelStyle,
+}
+
+/// Options for rendering a code frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeFrameOptions {
+  /// The number of lines to show above and below the labels.
+  pub context_lines: usize,
+  /// Whether to color the output with ANSI escape codes.
+  pub color: bool,
+}
+
+impl Default for CodeFrameOptions {
+  fn default() -> Self {
+    CodeFrameOptions {
+      context_lines: 2,
+      color: false,
+    }
+  }
+}
+
+/// Renders the lines of the labels with a gutter of line numbers and the
+/// ranges of the labels underlined.
+///
+/// The underlines are aligned using the display columns of `lines`, which
+/// must be the lines of `text`, and tabs are expanded to spaces in the
+/// same way. Lines that aren't near each other are separated by `...`.
+pub fn render_code_frame(
+  text: &str,
+  lines: &TextLines,
+  labels: &[Label],
+  options: &CodeFrameOptions,
+) -> String {
+  unwrap_or_panic(try_render_code_frame(text, lines, labels, options))
+}
+
+/// Fallible version of [`render_code_frame`].
+pub fn try_render_code_frame(
+  text: &str,
+  lines: &TextLines,
+  labels: &[Label],
+  options: &CodeFrameOptions,
+) -> Result<String, TextLinesError> {
+  if text.len() != lines.text_length() {
+    return Err(TextLinesError::TextLengthMismatch {
+      expected: lines.text_length(),
+      actual: text.len(),
+    });
+  }
+  let mut label_segments = Vec::with_capacity(labels.len());
+  for label in labels {
+    label_segments.push((label, lines.try_line_segments(label.range)?));
+  }
+
+  // get the ranges of line indexes to show, merging the ones that touch
+  let mut line_ranges = label_segments
+    .iter()
+    .map(|(_, segments)| {
+      let first_line_index = segments.first().unwrap().line_index;
+      let last_line_index = segments.last().unwrap().line_index;
+      first_line_index.saturating_sub(options.context_lines)
+        ..std::cmp::min(
+          last_line_index + options.context_lines + 1,
+          lines.lines_count(),
+        )
+    })
+    .collect::<Vec<_>>();
+  line_ranges.sort_by_key(|line_range| line_range.start);
+  let mut merged_line_ranges: Vec<Range<usize>> = Vec::with_capacity(line_ranges.len());
+  for line_range in line_ranges {
+    match merged_line_ranges.last_mut() {
+      Some(last) if line_range.start <= last.end => {
+        last.end = std::cmp::max(last.end, line_range.end);
+      }
+      _ => merged_line_ranges.push(line_range),
+    }
+  }
+
+  let gutter_width = match merged_line_ranges.last() {
+    Some(line_range) => line_range.end.to_string().len(),
+    None => return Ok(String::new()),
+  };
+  let paint = |text: &str, color: &str| {
+    if options.color {
+      format!("{}{}{}", color, text, RESET_COLOR)
+    } else {
+      text.to_string()
+    }
+  };
+  let empty_gutter = paint(&format!("{:1$} |", "", gutter_width), GUTTER_COLOR);
+  let mut output = String::new();
+  for (i, line_range) in merged_line_ranges.into_iter().enumerate() {
+    if i > 0 {
+      writeln!(output, "{}", paint("...", GUTTER_COLOR)).unwrap();
+    }
+    for line_index in line_range {
+      let gutter = format!("{:>1$} |", line_index + 1, gutter_width);
+      let (line_start, line_end) = lines.line_range(line_index);
+      let line_text = lines.expand_tabs(text, line_start..line_end);
+      if line_text.is_empty() {
+        writeln!(output, "{}", paint(&gutter, GUTTER_COLOR)).unwrap();
+      } else {
+        writeln!(output, "{} {}", paint(&gutter, GUTTER_COLOR), line_text).unwrap();
+      }
+
+      let mut line_labels = label_segments
+        .iter()
+        .filter_map(|(label, segments)| {
+          segments
+            .iter()
+            .find(|segment| segment.line_index == line_index)
+            .map(|segment| (*label, segment))
+        })
+        .collect::<Vec<_>>();
+      line_labels.sort_by_key(|(_, segment)| segment.display_columns.start);
+      for (label, segment) in line_labels {
+        write_underline(&mut output, &empty_gutter, label, segment, &paint);
+      }
+    }
+  }
+  Ok(output)
+}
+
+fn write_underline(
+  output: &mut String,
+  empty_gutter: &str,
+  label: &Label,
+  segment: &LineSegment,
+  paint: &impl Fn(&str, &str) -> String,
+) {
+  let width = segment.display_columns.end - segment.display_columns.start;
+  if width == 0 && !segment.ends_here {
+    return; // nothing to underline on this line
+  }
+  let (marker, color) = match label.style {
+    LabelStyle::Primary => ("^", PRIMARY_COLOR),
+    LabelStyle::Secondary => ("~", SECONDARY_COLOR),
+  };
+  // always show a marker for an empty range
+  let mut underline = marker.repeat(std::cmp::max(width, 1));
+  if let (true, Some(message)) = (segment.ends_here, &label.message) {
+    underline.push(' ');
+    underline.push_str(message);
+  }
+  writeln!(
+    output,
+    "{} {}{}",
+    empty_gutter,
+    " ".repeat(segment.display_columns.start),
+    paint(&underline, color)
+  )
+  .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::TabDisplay;
+  use crate::TextLinesOptions;
+
+  fn label(range: Range<usize>, message: Option<&str>, style: LabelStyle) -> Label {
+    Label {
+      range: range.into(),
+      message: message.map(|message| message.to_string()),
+      style,
+    }
+  }
+
+  fn render(text: &str, labels: &[Label], options: &CodeFrameOptions) -> String {
+    render_code_frame(text, &TextLines::new(text), labels, options)
+  }
+
+  #[test]
+  fn single_line() {
+    let text = "let a = 1;\nlet b = \"x\" + 1;\nfoo();\nbar();\n";
+    let labels = [label(19..26, Some("cannot add"), LabelStyle::Primary)];
+    let options = CodeFrameOptions {
+      context_lines: 1,
+      ..Default::default()
+    };
+    assert_eq!(
+      render(text, &labels, &options),
+      concat!(
+        "1 | let a = 1;\n",
+        "2 | let b = \"x\" + 1;\n",
+        "  |         ^^^^^^^ cannot add\n",
+        "3 | foo();\n",
+      )
+    );
+  }
+
+  #[test]
+  fn tabs_and_wide_chars() {
+    let text = "\t中\tx";
+    for (tab_display, expected) in [
+      (
+        TabDisplay::FixedWidth,
+        concat!("1 |     中    x\n", "  |           ^\n"),
+      ),
+      (
+        TabDisplay::TabStops,
+        concat!("1 |     中  x\n", "  |         ^\n"),
+      ),
+    ] {
+      let lines = TextLines::with_options(
+        text,
+        TextLinesOptions {
+          tab_display,
+          ..Default::default()
+        },
+      );
+      let labels = [label(5..6, None, LabelStyle::Primary)];
+      assert_eq!(
+        render_code_frame(text, &lines, &labels, &Default::default()),
+        expected
+      );
+    }
+  }
+
+  #[test]
+  fn multi_line_and_multiple_labels() {
+    let text = "1\nfn a() {\n  b\n}\n5\n6\n7\n8\n9\n10\n11";
+    let labels = [
+      label(2..16, Some("function"), LabelStyle::Primary),
+      label(6..8, Some("parens"), LabelStyle::Secondary),
+      label(28..28, Some("here"), LabelStyle::Primary),
+    ];
+    assert_eq!(
+      render(text, &labels, &Default::default()),
+      concat!(
+        " 1 | 1\n",
+        " 2 | fn a() {\n",
+        "   | ^^^^^^^^\n",
+        "   |     ~~ parens\n",
+        " 3 |   b\n",
+        "   | ^^^\n",
+        " 4 | }\n",
+        "   | ^ function\n",
+        " 5 | 5\n",
+        " 6 | 6\n",
+        "...\n",
+        " 8 | 8\n",
+        " 9 | 9\n",
+        "10 | 10\n",
+        "   |  ^ here\n",
+        "11 | 11\n",
+      )
+    );
+  }
+
+  #[test]
+  fn color() {
+    let text = "a b";
+    let labels = [label(2..3, Some("b"), LabelStyle::Primary)];
+    let options = CodeFrameOptions {
+      color: true,
+      ..Default::default()
+    };
+    assert_eq!(
+      render(text, &labels, &options),
+      concat!(
+        "\x1b[1;34m1 |\x1b[0m a b\n",
+        "\x1b[1;34m  |\x1b[0m   \x1b[1;31m^ b\x1b[0m\n",
+      )
+    );
+  }
+
+  #[test]
+  fn errors() {
+    let text = "ab";
+    let lines = TextLines::new(text);
+    let labels = [label(0..3, None, LabelStyle::Primary)];
+    assert_eq!(
+      try_render_code_frame(text, &lines, &labels, &Default::default()),
+      Err(TextLinesError::ByteIndexOutOfRange {
+        byte_index: 3,
+        text_length: 2,
+      })
+    );
+    assert_eq!(
+      try_render_code_frame("a", &lines, &[], &Default::default()),
+      Err(TextLinesError::TextLengthMismatch {
+        expected: 2,
+        actual: 1,
+      })
+    );
+    assert_eq!(
+      render_code_frame(text, &lines, &[], &Default::default()),
+      ""
+    );
+  }
+}
@@ -1,10 +1,60 @@
+use std::fmt;
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use line_table::scan_lines;
+use line_table::utf8_chunks;
+use line_table::LinePosition;
+use line_table::LineTable;
+use line_table::TextLine;
+
+mod ascii_scan;
+mod builder;
+#[cfg(feature = "code_frame")]
+mod code_frame;
+mod line_reader;
+mod line_starts;
+mod line_table;
+mod range;
+mod source_map;
+#[cfg(test)]
+mod test_helpers;
+mod text_with_lines;
+mod utf16_text_lines;
+
+pub use builder::TextLinesBuilder;
+#[cfg(feature = "code_frame")]
+pub use code_frame::render_code_frame;
+#[cfg(feature = "code_frame")]
+pub use code_frame::try_render_code_frame;
+#[cfg(feature = "code_frame")]
+pub use code_frame::CodeFrameOptions;
+#[cfg(feature = "code_frame")]
+pub use code_frame::Label;
+#[cfg(feature = "code_frame")]
+pub use code_frame::LabelStyle;
+pub use line_reader::LineReader;
+pub use line_starts::LineStarts;
+pub use range::ByteRange;
+pub use range::DisplayRange;
+pub use range::LineAndColumnRange;
+pub use range::LineSegment;
+pub use source_map::Mapping;
+pub use source_map::OriginalLocation;
+pub use source_map::SourceMap;
+pub use source_map::SourceMapBuilder;
+pub use source_map::SourceMapError;
+pub use text_with_lines::TextWithLines;
+pub use utf16_text_lines::Utf16TextLines;
+
 const BOM_CHAR: char = '\u{FEFF}';
 
 #[cfg_attr(
   feature = "serialization",
   derive(serde::Serialize, serde::Deserialize)
 )]
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct LineAndColumnIndex {
   /// The zero-indexed line index.
   pub line_index: usize,
@@ -16,7 +66,7 @@ pub struct LineAndColumnIndex {
   feature = "serialization",
   derive(serde::Serialize, serde::Deserialize)
 )]
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct LineAndColumnDisplay {
   /// The 1-indexed line number for display purposes.
   pub line_number: usize,
@@ -24,27 +74,305 @@ pub struct LineAndColumnDisplay {
   pub column_number: usize,
 }
 
-#[derive(Debug)]
-struct MultiByteCharInfo {
-  /// The byte index in the entire file.
-  byte_index: usize,
-  /// The character index on the current line.
-  line_char_index: usize,
-  length: usize,
+/// A view of a line that's horizontally truncated to fit within a display width.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TruncatedLine {
+  /// The text of the view with tabs expanded to spaces and `…` markers
+  /// where text was left out.
+  pub text: String,
+  /// The byte range of the line that's in the view.
+  pub byte_range: ByteRange,
+  /// The zero-indexed display column of the provided byte index in the view.
+  pub display_index: usize,
+}
+
+/// The unit a column index is measured in.
+#[cfg_attr(
+  feature = "serialization",
+  derive(serde::Serialize, serde::Deserialize)
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnEncoding {
+  /// UTF-8 code units (bytes).
+  Utf8,
+  /// UTF-16 code units. This is the default for the Language Server Protocol.
+  Utf16,
+  /// Unicode scalar values (chars).
+  Utf32,
+  /// Extended grapheme clusters as defined by UAX #29, which are
+  /// user-perceived characters.
+  Graphemes,
+}
+
+/// What a column is measured in.
+#[derive(Debug, Clone, Copy)]
+enum ColumnMeasure {
+  Encoding(ColumnEncoding),
+  /// The number of terminal cells.
+  DisplayWidth,
+}
+
+/// The characters that end a line.
+#[cfg_attr(
+  feature = "serialization",
+  derive(serde::Serialize, serde::Deserialize)
+)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineTerminators {
+  /// Only `\n`. A `\r` before it is part of the line.
+  Lf,
+  /// `\n` and `\r\n`.
+  #[default]
+  LfAndCrLf,
+  /// `\n`, `\r\n` and a lone `\r`.
+  Universal,
+  /// The ECMAScript line terminators, which are `\n`, `\r\n`, a lone `\r`,
+  /// the line separator (U+2028) and the paragraph separator (U+2029).
+  EcmaScript,
+  /// All the Unicode line terminators, which are the ECMAScript line terminators
+  /// along with vertical tab (U+000B), form feed (U+000C) and next line (U+0085).
+  Unicode,
+}
+
+impl LineTerminators {
+  fn is_lone_cr_terminator(self) -> bool {
+    match self {
+      LineTerminators::Lf | LineTerminators::LfAndCrLf => false,
+      LineTerminators::Universal | LineTerminators::EcmaScript | LineTerminators::Unicode => true,
+    }
+  }
+
+  /// Gets if the character is a line terminator other than `\n` or `\r`.
+  fn is_other_terminator(self, c: char) -> bool {
+    match self {
+      LineTerminators::Lf | LineTerminators::LfAndCrLf | LineTerminators::Universal => false,
+      LineTerminators::EcmaScript => matches!(c, '\u{2028}' | '\u{2029}'),
+      LineTerminators::Unicode => {
+        matches!(c, '\u{0B}' | '\u{0C}' | '\u{85}' | '\u{2028}' | '\u{2029}')
+      }
+    }
+  }
+}
+
+/// How a tab character is displayed.
+#[cfg_attr(
+  feature = "serialization",
+  derive(serde::Serialize, serde::Deserialize)
+)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TabDisplay {
+  /// Each tab takes up the indent width.
+  #[default]
+  FixedWidth,
+  /// Each tab moves to the next multiple of the indent width, which
+  /// is how most terminals and editors display tabs.
+  TabStops,
+}
+
+impl TabDisplay {
+  /// Gets the width of a tab at the provided zero-indexed display column.
+  fn tab_width(self, display_index: usize, indent_width: usize) -> usize {
+    match self {
+      TabDisplay::FixedWidth => indent_width,
+      // move to the next tab stop
+      TabDisplay::TabStops => match display_index.checked_rem(indent_width) {
+        Some(remainder) => indent_width - remainder,
+        None => 0,
+      },
+    }
+  }
+}
+
+/// How to resolve a display column that lands inside a character taking
+/// up multiple display columns, such as a tab or a wide character.
+#[cfg_attr(
+  feature = "serialization",
+  derive(serde::Serialize, serde::Deserialize)
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplaySnap {
+  /// Resolve to the start of the character.
+  Left,
+  /// Resolve to the end of the character.
+  Right,
+  /// Don't resolve the display column.
+  Error,
+}
+
+/// An error from providing an invalid position to a `TextLines`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextLinesError {
+  /// The byte index was greater than the text length.
+  ByteIndexOutOfRange {
+    byte_index: usize,
+    text_length: usize,
+  },
+  /// The line index was greater than or equal to the number of lines.
+  LineIndexOutOfRange {
+    line_index: usize,
+    lines_count: usize,
+  },
+  /// The byte index was not on a character boundary.
+  NotCharBoundary { byte_index: usize },
+  /// The range start was greater than its end.
+  InvalidRange { start: usize, end: usize },
+  /// The text length did not match the expected length.
+  TextLengthMismatch { expected: usize, actual: usize },
+  /// The display column was inside a character that takes up multiple
+  /// display columns, such as a tab or a wide character.
+  DisplayColumnInsideChar {
+    line_number: usize,
+    column_number: usize,
+  },
+  /// The 1-indexed line number or column number was zero.
+  InvalidLineAndColumnDisplay {
+    line_number: usize,
+    column_number: usize,
+  },
+  /// The updated text of an edit did not contain the new text in the range.
+  EditTextMismatch { start: usize, end: usize },
+  /// The bytes were not valid UTF-8 starting at the byte index.
+  InvalidUtf8 { byte_index: usize },
+  /// The UTF-16 index was greater than the text length in UTF-16 code units.
+  Utf16IndexOutOfRange {
+    utf16_index: usize,
+    text_length: usize,
+  },
+  /// The maximum display width was too small to show a character between
+  /// the markers of a truncated line.
+  MaxWidthTooSmall { max_width: usize, min_width: usize },
+  /// The source index was greater than or equal to the number of sources.
+  SourceIndexOutOfRange {
+    source_index: usize,
+    sources_count: usize,
+  },
+}
+
+impl fmt::Display for TextLinesError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TextLinesError::ByteIndexOutOfRange {
+        byte_index,
+        text_length,
+      } => write!(
+        f,
+        "The specified byte index {} was greater than the text length of {}.",
+        byte_index, text_length
+      ),
+      TextLinesError::LineIndexOutOfRange {
+        line_index,
+        lines_count,
+      } => write!(
+        f,
+        "The specified line index {} was greater or equal to the number of lines of {}.",
+        line_index, lines_count
+      ),
+      TextLinesError::NotCharBoundary { byte_index } => write!(
+        f,
+        "The specified byte index {} was not on a character boundary.",
+        byte_index
+      ),
+      TextLinesError::InvalidRange { start, end } => write!(
+        f,
+        "The specified range start {} was greater than its end {}.",
+        start, end
+      ),
+      TextLinesError::TextLengthMismatch { expected, actual } => write!(
+        f,
+        "The text length of {} did not match the expected length of {}.",
+        actual, expected
+      ),
+      TextLinesError::DisplayColumnInsideChar {
+        line_number,
+        column_number,
+      } => write!(
+        f,
+        "The specified display column {} on line {} was inside a character.",
+        column_number, line_number
+      ),
+      TextLinesError::InvalidLineAndColumnDisplay {
+        line_number,
+        column_number,
+      } => write!(
+        f,
+        "The specified line number {} and column number {} must both be 1 or greater.",
+        line_number, column_number
+      ),
+      TextLinesError::EditTextMismatch { start, end } => write!(
+        f,
+        "The updated text from byte index {} to {} did not match the new text.",
+        start, end
+      ),
+      TextLinesError::InvalidUtf8 { byte_index } => write!(
+        f,
+        "The text was not valid UTF-8 at byte index {}.",
+        byte_index
+      ),
+      TextLinesError::Utf16IndexOutOfRange {
+        utf16_index,
+        text_length,
+      } => write!(
+        f,
+        "The specified UTF-16 index {} was greater than the text length of {}.",
+        utf16_index, text_length
+      ),
+      TextLinesError::MaxWidthTooSmall {
+        max_width,
+        min_width,
+      } => write!(
+        f,
+        "The specified maximum width {} was less than the minimum width of {}.",
+        max_width, min_width
+      ),
+      TextLinesError::SourceIndexOutOfRange {
+        source_index,
+        sources_count,
+      } => write!(
+        f,
+        "The specified source index {} was greater or equal to the number of sources of {}.",
+        source_index, sources_count
+      ),
+    }
+  }
+}
+
+impl std::error::Error for TextLinesError {}
+
+fn unwrap_or_panic<T>(result: Result<T, TextLinesError>) -> T {
+  match result {
+    Ok(value) => value,
+    Err(err) => panic!("{}", err),
+  }
+}
+
+/// Options for creating a `TextLines`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextLinesOptions {
+  /// The width of a tab character when getting the display column.
+  pub indent_width: usize,
+  /// How a tab character is displayed when getting the display column.
+  pub tab_display: TabDisplay,
+  /// The characters that end a line.
+  pub line_terminators: LineTerminators,
 }
 
-#[derive(Debug)]
-struct TextLine {
-  start_index: usize,
-  end_index: usize,
-  multi_byte_chars: Vec<MultiByteCharInfo>,
-  tab_chars: Vec<usize>,
+impl Default for TextLinesOptions {
+  fn default() -> Self {
+    TextLinesOptions {
+      indent_width: 4,
+      tab_display: TabDisplay::default(),
+      line_terminators: LineTerminators::default(),
+    }
+  }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TextLines {
-  lines: Vec<TextLine>,
-  indent_width: usize,
+  lines: LineTable,
+  options: TextLinesOptions,
+  /// The last grapheme cluster of the text, which may join with
+  /// appended text.
+  last_grapheme: Vec<u8>,
 }
 
 impl TextLines {
@@ -58,57 +386,188 @@ impl TextLines {
   /// The indent width sets the width of a tab character when getting
   /// the display column.
   pub fn with_indent_width(text: &str, indent_width: usize) -> Self {
-    let mut last_line_start = if text.starts_with(BOM_CHAR) {
-      BOM_CHAR.len_utf8()
+    TextLines::with_options(
+      text,
+      TextLinesOptions {
+        indent_width,
+        ..Default::default()
+      },
+    )
+  }
+
+  /// Creates a new `TextLines` with the specified text and options.
+  pub fn with_options(text: &str, options: TextLinesOptions) -> Self {
+    TextLines::scan(text.as_bytes(), options, last_grapheme(text))
+  }
+
+  /// Creates a new `TextLines` with the specified bytes, which may not be
+  /// valid UTF-8, and options.
+  ///
+  /// The lines are indexed by the byte indexes of the provided bytes and
+  /// each invalid UTF-8 sequence is counted as a single replacement
+  /// character (`U+FFFD`), which takes up one display column and is a
+  /// grapheme cluster on its own. The invalid sequences are the same ones
+  /// replaced by `String::from_utf8_lossy`.
+  pub fn from_bytes(bytes: &[u8], options: TextLinesOptions) -> Self {
+    TextLines::scan(bytes, options, last_grapheme_of_bytes(bytes))
+  }
+
+  fn scan(bytes: &[u8], options: TextLinesOptions, last_grapheme: Vec<u8>) -> Self {
+    let (mut lines, _) = scan_lines(bytes, LinePosition::line_start(0, 0), &options, |_| false);
+    lines.shrink_to_fit();
+
+    Self {
+      lines,
+      options,
+      last_grapheme,
+    }
+  }
+
+  /// Creates a new `TextLines` from the text read from the reader without
+  /// holding the entire text in memory.
+  ///
+  /// An error of kind `InvalidData` is returned when the text is not valid UTF-8.
+  pub fn from_reader(
+    reader: impl std::io::Read,
+    options: TextLinesOptions,
+  ) -> std::io::Result<Self> {
+    let mut builder = TextLinesBuilder::new(options);
+    builder.read_from(reader)?;
+    builder
+      .finish()
+      .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+  }
+
+  /// Updates the lines after `text` was appended to the end of the text.
+  ///
+  /// Only the end of the last line is rescanned, which handles the appended
+  /// text continuing the last line or completing a `\r\n` newline, so the
+  /// result is the same as creating a new `TextLines` for the combined text.
+  pub fn append(&mut self, text: &str) {
+    if text.is_empty() {
+      return;
+    }
+
+    // rescan from the last grapheme cluster since it may join with the appended text
+    let mut scan_text = std::mem::take(&mut self.last_grapheme);
+    scan_text.extend_from_slice(text.as_bytes());
+    let scan_start = self.text_length() + text.len() - scan_text.len();
+    let start = if scan_start == 0 {
+      self.lines.truncate(0, 0);
+      LinePosition::line_start(0, 0) // rescan the BOM
     } else {
-      0
+      let line_index = self.line_index(scan_start);
+      let line = self.lines.line(line_index);
+      let start = LinePosition::in_line(&line, scan_start, &self.options);
+      self.lines.truncate(line_index, scan_start);
+      start
     };
-    let mut multi_byte_chars = Vec::new();
-    let mut tab_chars = Vec::new();
-    let mut lines = Vec::new();
-    let mut was_last_slash_r = false;
-    let mut line_char_index = 0;
-    for (char_index, (byte_index, c)) in text.char_indices().enumerate() {
-      if byte_index == 0 && c == BOM_CHAR {
-        continue;
-      }
-
-      if c == '\n' {
-        lines.push(TextLine {
-          start_index: last_line_start,
-          end_index: if was_last_slash_r {
-            byte_index - 1
-          } else {
-            byte_index
-          },
-          multi_byte_chars: std::mem::take(&mut multi_byte_chars),
-          tab_chars: std::mem::take(&mut tab_chars),
-        });
-        last_line_start = byte_index + 1;
-        line_char_index = char_index + 1;
-      } else if c == '\t' {
-        tab_chars.push(byte_index);
-      } else if c.len_utf8() > 1 {
-        multi_byte_chars.push(MultiByteCharInfo {
-          line_char_index: char_index - line_char_index,
-          byte_index,
-          length: c.len_utf8(),
-        });
+    let (new_lines, _) = scan_lines(&scan_text, start, &self.options, |_| false);
+    self.lines.extend(&new_lines);
+    self.last_grapheme = last_grapheme_of_bytes(&scan_text);
+  }
+
+  /// Updates the lines in place after the text in the provided byte range
+  /// was replaced with `new_text`.
+  ///
+  /// `updated_text` is the full text after the edit was applied. Only the
+  /// lines affected by the edit are rescanned and the lines after it are
+  /// shifted, so the result is the same as creating a new `TextLines` for
+  /// the updated text.
+  pub fn apply_edit(&mut self, range: Range<usize>, new_text: &str, updated_text: &str) {
+    unwrap_or_panic(self.try_apply_edit(range, new_text, updated_text))
+  }
+
+  /// Fallible version of [`apply_edit`](Self::apply_edit). The lines are
+  /// left unchanged when an error is returned.
+  pub fn try_apply_edit(
+    &mut self,
+    range: Range<usize>,
+    new_text: &str,
+    updated_text: &str,
+  ) -> Result<(), TextLinesError> {
+    if range.start > range.end {
+      return Err(TextLinesError::InvalidRange {
+        start: range.start,
+        end: range.end,
+      });
+    }
+    self.validate_byte_index(range.end)?;
+    let old_text_length = self.text_length();
+    let expected_length = old_text_length - (range.end - range.start) + new_text.len();
+    if updated_text.len() != expected_length {
+      return Err(TextLinesError::TextLengthMismatch {
+        expected: expected_length,
+        actual: updated_text.len(),
+      });
+    }
+    let new_text_end = range.start + new_text.len();
+    for byte_index in [range.start, new_text_end] {
+      if !updated_text.is_char_boundary(byte_index) {
+        return Err(TextLinesError::NotCharBoundary { byte_index });
       }
-      was_last_slash_r = c == '\r';
+    }
+    if &updated_text[range.start..new_text_end] != new_text {
+      return Err(TextLinesError::EditTextMismatch {
+        start: range.start,
+        end: new_text_end,
+      });
     }
 
-    lines.push(TextLine {
-      start_index: last_line_start,
-      end_index: text.len(),
-      multi_byte_chars,
-      tab_chars,
-    });
+    let mut first_line_index = self.line_index(range.start);
+    if first_line_index > 0 && range.start <= self.lines.line_start(first_line_index) {
+      // the edit may join with the previous line's newline (ex. inserting a \r before it)
+      first_line_index -= 1;
+    }
+    let (scan_start, scan_char_start) = if first_line_index == 0 {
+      (0, 0) // rescan the BOM
+    } else {
+      (
+        self.lines.line_start(first_line_index),
+        self.lines.line_char_start(first_line_index),
+      )
+    };
 
-    Self {
-      lines,
-      indent_width,
+    // rescan until reaching a line start after the edit that was also a line
+    // start before the edit, since all the lines from there on are the same
+    let old_lines = &self.lines;
+    let (new_lines, resync_index) = scan_lines(
+      &updated_text.as_bytes()[scan_start..],
+      LinePosition::line_start(scan_start, scan_char_start),
+      &self.options,
+      |line_start| {
+        line_start >= new_text_end && {
+          let old_line_start = line_start + range.end - new_text_end;
+          old_line_start <= old_text_length && {
+            let line_index = old_lines
+              .partition_point_line_start(first_line_index..old_lines.len(), old_line_start);
+            old_lines.line_start(line_index - 1) == old_line_start
+          }
+        }
+      },
+    );
+
+    if let Some((line_start, line_char_start)) = resync_index {
+      let old_line_start = line_start + range.end - new_text_end;
+      let end_line_index = self.line_index(old_line_start);
+      let old_line_char_start = self.lines.line_char_start(end_line_index);
+      self.lines.splice_lines(
+        first_line_index..end_line_index,
+        &new_lines,
+        (old_line_start, line_start),
+        (old_line_char_start, line_char_start),
+      );
+    } else {
+      let no_shift = (0, 0);
+      self.lines.splice_lines(
+        first_line_index..self.lines.len(),
+        &new_lines,
+        no_shift,
+        no_shift,
+      );
     }
+    self.last_grapheme = last_grapheme(updated_text);
+    Ok(())
   }
 
   /// Gets the number of lines in the text.
@@ -118,179 +577,213 @@ impl TextLines {
 
   /// Gets the text length in bytes.
   pub fn text_length(&self) -> usize {
-    self.lines.last().unwrap().end_index
+    self.lines.text_length()
+  }
+
+  /// Gets the approximate number of bytes of memory used, including the
+  /// heap allocations for the information about the lines.
+  ///
+  /// Offsets are stored in 8 or 32 bits when they fit, so a text under
+  /// 4 GiB never stores them in 64 bits.
+  pub fn memory_usage(&self) -> usize {
+    std::mem::size_of::<Self>() + self.lines.heap_size() + self.last_grapheme.capacity()
   }
 
   /// Gets the line index from a byte index.
   /// Note that if you provide the middle byte index of a \r\n newline
   /// then it will return the index of the preceding line.
   pub fn line_index(&self, byte_index: usize) -> usize {
-    self.assert_valid_byte_index(byte_index);
+    unwrap_or_panic(self.try_line_index(byte_index))
+  }
 
-    match self
-      .lines
-      .binary_search_by_key(&byte_index, |line| line.start_index)
-    {
-      Ok(index) => index,
-      Err(insert_index) => {
-        if insert_index == 0 {
-          0 // may happen when there's a BOM
-        } else {
-          insert_index - 1
-        }
-      }
-    }
+  /// Fallible version of [`line_index`](Self::line_index).
+  pub fn try_line_index(&self, byte_index: usize) -> Result<usize, TextLinesError> {
+    self.validate_byte_index(byte_index)?;
+    Ok(self.lines.line_index(byte_index))
   }
 
   /// Gets the line start byte index.
   pub fn line_start(&self, line_index: usize) -> usize {
-    self.assert_valid_line_index(line_index);
-    self.lines[line_index].start_index
+    unwrap_or_panic(self.try_line_start(line_index))
+  }
+
+  /// Fallible version of [`line_start`](Self::line_start).
+  pub fn try_line_start(&self, line_index: usize) -> Result<usize, TextLinesError> {
+    Ok(self.get_line(line_index)?.start_index)
   }
 
   /// Gets the line end byte index (before/at the newline character).
   pub fn line_end(&self, line_index: usize) -> usize {
-    self.assert_valid_line_index(line_index);
-    self.lines[line_index].end_index
+    unwrap_or_panic(self.try_line_end(line_index))
+  }
+
+  /// Fallible version of [`line_end`](Self::line_end).
+  pub fn try_line_end(&self, line_index: usize) -> Result<usize, TextLinesError> {
+    Ok(self.get_line(line_index)?.end_index)
   }
 
   /// Gets the line range.
   pub fn line_range(&self, line_index: usize) -> (usize, usize) {
-    self.assert_valid_line_index(line_index);
-    let line = &self.lines[line_index];
-    (line.start_index, line.end_index)
+    unwrap_or_panic(self.try_line_range(line_index))
+  }
+
+  /// Fallible version of [`line_range`](Self::line_range).
+  pub fn try_line_range(&self, line_index: usize) -> Result<(usize, usize), TextLinesError> {
+    let line = self.get_line(line_index)?;
+    Ok((line.start_index, line.end_index))
   }
 
   /// Gets the byte position from the provided line and column index.
   pub fn byte_index(&self, line_and_column: LineAndColumnIndex) -> usize {
-    let line = &self.lines[line_and_column.line_index];
-    let mut byte_index = line.start_index + line_and_column.column_index;
+    self.byte_index_with_encoding(line_and_column, ColumnEncoding::Utf32)
+  }
 
-    for char_info in line.multi_byte_chars.iter() {
-      if char_info.line_char_index < line_and_column.column_index {
-        // - 1 because the 1 was already added above when adding the column index
-        byte_index += char_info.length - 1;
-      } else {
-        break;
-      }
-    }
+  /// Fallible version of [`byte_index`](Self::byte_index).
+  pub fn try_byte_index(
+    &self,
+    line_and_column: LineAndColumnIndex,
+  ) -> Result<usize, TextLinesError> {
+    self.try_byte_index_with_encoding(line_and_column, ColumnEncoding::Utf32)
+  }
 
-    // fallback gracefully to the end index of the line when the column goes off
-    if byte_index > line.end_index {
-      line.end_index
-    } else {
-      byte_index
-    }
+  /// Gets the byte position from the provided line and UTF-16 code unit column index.
+  ///
+  /// A column in the middle of a surrogate pair will resolve to the
+  /// start of the character.
+  pub fn byte_index_from_utf16(&self, line_and_column: LineAndColumnIndex) -> usize {
+    self.byte_index_with_encoding(line_and_column, ColumnEncoding::Utf16)
   }
 
-  /// Gets a byte index from the provided character index.
-  pub fn byte_index_from_char_index(&self, char_index: usize) -> usize {
-    let mut last_char_index = 0;
-    let mut last_byte_index = 0;
+  /// Fallible version of [`byte_index_from_utf16`](Self::byte_index_from_utf16).
+  pub fn try_byte_index_from_utf16(
+    &self,
+    line_and_column: LineAndColumnIndex,
+  ) -> Result<usize, TextLinesError> {
+    self.try_byte_index_with_encoding(line_and_column, ColumnEncoding::Utf16)
+  }
 
-    let mut lines = self.lines.iter().peekable();
-    while let Some(line) = lines.next() {
-      for char_info in &line.multi_byte_chars {
-        let char_length = char_info.byte_index - last_byte_index;
-        if last_char_index + char_length >= char_index {
-          let byte_diff = char_index - last_char_index;
-          return last_byte_index + byte_diff;
-        } else {
-          // move to the position past the character
-          last_byte_index = char_info.byte_index + char_info.length;
-          last_char_index += char_length + 1;
-        }
-      }
+  /// Gets the byte position from the provided line and column index where
+  /// the column is measured in the provided encoding.
+  ///
+  /// A column in the middle of a character will resolve to the start
+  /// of the character.
+  pub fn byte_index_with_encoding(
+    &self,
+    line_and_column: LineAndColumnIndex,
+    encoding: ColumnEncoding,
+  ) -> usize {
+    unwrap_or_panic(self.try_byte_index_with_encoding(line_and_column, encoding))
+  }
 
-      // check the end of the line
-      let line_end = if let Some(next_line) = lines.peek() {
-        next_line.start_index
-      } else {
-        line.end_index
-      };
-      let char_length = line_end - last_byte_index;
-      if last_char_index + char_length >= char_index {
-        let byte_diff = char_index - last_char_index;
-        return last_byte_index + byte_diff;
-      } else {
-        last_byte_index = line_end;
-        last_char_index += char_length;
-      }
-    }
+  /// Fallible version of [`byte_index_with_encoding`](Self::byte_index_with_encoding).
+  pub fn try_byte_index_with_encoding(
+    &self,
+    line_and_column: LineAndColumnIndex,
+    encoding: ColumnEncoding,
+  ) -> Result<usize, TextLinesError> {
+    let line = self.get_line(line_and_column.line_index)?;
+    Ok(line.byte_index(line_and_column.column_index, encoding))
+  }
 
-    last_byte_index
+  /// Gets a byte index from the provided character index.
+  pub fn byte_index_from_char_index(&self, char_index: usize) -> usize {
+    let line_index = match self
+      .lines
+      .partition_point_line_char_start(char_index)
+      .checked_sub(1)
+    {
+      Some(line_index) => line_index,
+      None => return char_index, // within the BOM
+    };
+    let byte_index = self
+      .lines
+      .line(line_index)
+      .byte_index_from_char_index(char_index);
+    std::cmp::min(byte_index, self.text_length())
   }
 
   /// Gets a character index from the provided byte index.
   pub fn char_index(&self, byte_index: usize) -> usize {
-    let mut lines = self.lines.iter().peekable();
-    let mut last_char_index = 0;
-    let mut last_byte_index = 0;
-    while let Some(line) = lines.next() {
-      for char_info in &line.multi_byte_chars {
-        if char_info.byte_index >= byte_index {
-          let char_length = byte_index - last_byte_index;
-          return last_char_index + char_length;
-        } else {
-          // move to the position past the character
-          let char_length = char_info.byte_index - last_byte_index;
-          last_byte_index = char_info.byte_index + char_info.length;
-          last_char_index += char_length + 1;
-        }
-      }
-
-      // check the end of the line
-      let line_end = if let Some(next_line) = lines.peek() {
-        next_line.start_index
-      } else {
-        line.end_index
-      };
-      if line_end >= byte_index {
-        let char_length = byte_index - last_byte_index;
-        return last_char_index + char_length;
-      } else {
-        let char_length = line_end - last_byte_index;
-        last_byte_index = line_end;
-        last_char_index += char_length;
-      }
+    let byte_index = std::cmp::min(byte_index, self.text_length());
+    let line = self.lines.line(self.line_index(byte_index));
+    if byte_index < line.start_index {
+      byte_index // within the BOM
+    } else {
+      line.char_index(byte_index)
     }
-
-    last_char_index
   }
 
   /// Gets the line and column index of the provided byte index.
   pub fn line_and_column_index(&self, byte_index: usize) -> LineAndColumnIndex {
-    // ensure no panics will happen here in case someone is specifying a byte position in the middle of a char
-    let line_index = self.line_index(byte_index);
-    let line = &self.lines[line_index];
+    self.line_and_column_index_with_encoding(byte_index, ColumnEncoding::Utf32)
+  }
 
-    let relative_byte_index = if byte_index < line.start_index {
-      0 // could happen when at the BOM position
-    } else {
-      byte_index - line.start_index
-    };
-    let multi_byte_char_offset = line
-      .multi_byte_chars
-      .iter()
-      .take_while(|char_info| char_info.byte_index < byte_index)
-      .map(|char_info| {
-        if char_info.byte_index + char_info.length > byte_index {
-          byte_index - char_info.byte_index
-        } else {
-          char_info.length - 1
-        }
-      })
-      .sum::<usize>();
+  /// Fallible version of [`line_and_column_index`](Self::line_and_column_index).
+  pub fn try_line_and_column_index(
+    &self,
+    byte_index: usize,
+  ) -> Result<LineAndColumnIndex, TextLinesError> {
+    self.try_line_and_column_index_with_encoding(byte_index, ColumnEncoding::Utf32)
+  }
+
+  /// Gets the line and UTF-16 code unit column index of the provided byte index.
+  ///
+  /// This is useful for the Language Server Protocol, which defaults to
+  /// UTF-16 code unit columns.
+  pub fn line_and_utf16_column_index(&self, byte_index: usize) -> LineAndColumnIndex {
+    self.line_and_column_index_with_encoding(byte_index, ColumnEncoding::Utf16)
+  }
+
+  /// Fallible version of [`line_and_utf16_column_index`](Self::line_and_utf16_column_index).
+  pub fn try_line_and_utf16_column_index(
+    &self,
+    byte_index: usize,
+  ) -> Result<LineAndColumnIndex, TextLinesError> {
+    self.try_line_and_column_index_with_encoding(byte_index, ColumnEncoding::Utf16)
+  }
+
+  /// Gets the line and column index of the provided byte index where the
+  /// column is measured in the provided encoding.
+  ///
+  /// A byte index in the middle of a character resolves to the column
+  /// of that character.
+  pub fn line_and_column_index_with_encoding(
+    &self,
+    byte_index: usize,
+    encoding: ColumnEncoding,
+  ) -> LineAndColumnIndex {
+    unwrap_or_panic(self.try_line_and_column_index_with_encoding(byte_index, encoding))
+  }
 
-    LineAndColumnIndex {
+  /// Fallible version of [`line_and_column_index_with_encoding`](Self::line_and_column_index_with_encoding).
+  pub fn try_line_and_column_index_with_encoding(
+    &self,
+    byte_index: usize,
+    encoding: ColumnEncoding,
+  ) -> Result<LineAndColumnIndex, TextLinesError> {
+    // ensure no panics will happen here in case someone is specifying a byte position in the middle of a char
+    let line_index = self.try_line_index(byte_index)?;
+    let line = self.lines.line(line_index);
+    Ok(LineAndColumnIndex {
       line_index,
-      column_index: relative_byte_index - multi_byte_char_offset,
-    }
+      column_index: line.column_index(byte_index, ColumnMeasure::Encoding(encoding)),
+    })
   }
 
   /// Gets the line and column display based on the indentation width and the provided byte index.
+  ///
+  /// East Asian wide characters take up two columns and zero-width characters
+  /// such as combining marks take up none.
   pub fn line_and_column_display(&self, byte_index: usize) -> LineAndColumnDisplay {
-    self.line_and_column_display_with_indent_width(byte_index, self.indent_width)
+    self.line_and_column_display_with_indent_width(byte_index, self.options.indent_width)
+  }
+
+  /// Fallible version of [`line_and_column_display`](Self::line_and_column_display).
+  pub fn try_line_and_column_display(
+    &self,
+    byte_index: usize,
+  ) -> Result<LineAndColumnDisplay, TextLinesError> {
+    self.try_line_and_column_display_with_indent_width(byte_index, self.options.indent_width)
   }
 
   /// Gets the line and column display based on the provided byte index and indentation width.
@@ -299,52 +792,532 @@ impl TextLines {
     byte_index: usize,
     indent_width: usize,
   ) -> LineAndColumnDisplay {
-    let line_and_column_index = self.line_and_column_index(byte_index);
-    let line = &self.lines[line_and_column_index.line_index];
-    let tab_char_count = line
-      .tab_chars
-      .iter()
-      .take_while(|tab_index| **tab_index < byte_index)
-      .count();
+    unwrap_or_panic(self.try_line_and_column_display_with_indent_width(byte_index, indent_width))
+  }
 
-    LineAndColumnDisplay {
-      line_number: line_and_column_index.line_index + 1,
-      column_number: line_and_column_index.column_index - tab_char_count
-        + tab_char_count * indent_width
-        + 1,
-    }
+  /// Fallible version of [`line_and_column_display_with_indent_width`](Self::line_and_column_display_with_indent_width).
+  pub fn try_line_and_column_display_with_indent_width(
+    &self,
+    byte_index: usize,
+    indent_width: usize,
+  ) -> Result<LineAndColumnDisplay, TextLinesError> {
+    self.get_line_and_column_display(byte_index, indent_width, ColumnMeasure::DisplayWidth)
   }
 
-  fn assert_valid_byte_index(&self, byte_index: usize) {
-    if byte_index > self.text_length() {
-      panic!(
-        "The specified byte index {} was greater than the text length of {}.",
-        byte_index,
-        self.text_length()
-      )
-    }
+  /// Gets the line and column display based on the indentation width and the
+  /// provided byte index where non-tab characters are measured in the provided
+  /// encoding instead of by their display width.
+  pub fn line_and_column_display_with_encoding(
+    &self,
+    byte_index: usize,
+    encoding: ColumnEncoding,
+  ) -> LineAndColumnDisplay {
+    unwrap_or_panic(self.try_line_and_column_display_with_encoding(byte_index, encoding))
   }
 
-  fn assert_valid_line_index(&self, line_index: usize) {
-    if line_index >= self.lines.len() {
-      panic!(
-        "The specified line index {} was greater or equal to the number of lines of {}.",
-        line_index,
-        self.lines.len()
-      );
+  /// Fallible version of [`line_and_column_display_with_encoding`](Self::line_and_column_display_with_encoding).
+  pub fn try_line_and_column_display_with_encoding(
+    &self,
+    byte_index: usize,
+    encoding: ColumnEncoding,
+  ) -> Result<LineAndColumnDisplay, TextLinesError> {
+    self.get_line_and_column_display(
+      byte_index,
+      self.options.indent_width,
+      ColumnMeasure::Encoding(encoding),
+    )
+  }
+
+  /// Gets the byte index from the provided line and column display based on
+  /// the indentation width.
+  ///
+  /// This is the reverse of `line_and_column_display`. A column inside the
+  /// expansion of a tab or a wide character is resolved based on the provided
+  /// snap and `None` is returned when it's `DisplaySnap::Error`. A column past
+  /// the end of the line falls back to the end index of the line.
+  ///
+  /// Panics when the line number or column number is zero or when the line
+  /// number is greater than the number of lines. Use
+  /// `try_byte_index_from_display` to get an error in every case instead.
+  pub fn byte_index_from_display(
+    &self,
+    line_and_column: LineAndColumnDisplay,
+    snap: DisplaySnap,
+  ) -> Option<usize> {
+    match self.try_byte_index_from_display(line_and_column, snap) {
+      Ok(byte_index) => Some(byte_index),
+      Err(TextLinesError::DisplayColumnInsideChar { .. }) => None,
+      Err(err) => panic!("{}", err),
     }
   }
-}
 
-#[cfg(test)]
-mod tests {
-  use super::*;
+  /// Fallible version of [`byte_index_from_display`](Self::byte_index_from_display),
+  /// which returns an error instead of `None` for a column inside a character.
+  pub fn try_byte_index_from_display(
+    &self,
+    line_and_column: LineAndColumnDisplay,
+    snap: DisplaySnap,
+  ) -> Result<usize, TextLinesError> {
+    if line_and_column.line_number == 0 || line_and_column.column_number == 0 {
+      return Err(TextLinesError::InvalidLineAndColumnDisplay {
+        line_number: line_and_column.line_number,
+        column_number: line_and_column.column_number,
+      });
+    }
+    let line = self.get_line(line_and_column.line_number - 1)?;
+    let target_index = line_and_column.column_number - 1;
+    let mut tab_chars = line.tab_chars().peekable();
+    let mut multi_byte_chars = line.multi_byte_chars().peekable();
+    let mut byte_index = line.start_index;
+    let mut display_index = 0;
+
+    loop {
+      // get the next character that may not take up a single display column
+      let next_tab_index = tab_chars.peek().map(|tab_char| tab_char.byte_index);
+      let next_char_info = multi_byte_chars.peek().copied();
+      let (char_index, char_length, char_width) = match (next_tab_index, next_char_info) {
+        (Some(tab_index), Some(char_info)) if tab_index > char_info.byte_index => {
+          multi_byte_chars.next();
+          (
+            char_info.byte_index,
+            char_info.length,
+            char_info.display_width,
+          )
+        }
+        (Some(tab_index), _) => {
+          tab_chars.next();
+          (
+            tab_index,
+            1,
+            self.options.tab_display.tab_width(
+              display_index + tab_index - byte_index,
+              self.options.indent_width,
+            ),
+          )
+        }
+        (None, Some(char_info)) => {
+          multi_byte_chars.next();
+          (
+            char_info.byte_index,
+            char_info.length,
+            char_info.display_width,
+          )
+        }
+        (None, None) => break,
+      };
+      if char_index >= line.end_index {
+        break;
+      }
 
-  #[test]
-  fn line_and_column_index() {
-    let text = "12\n3\r\n4\n5";
-    let info = TextLines::new(text);
-    assert_line_and_col_index(&info, 0, 0, 0); // 1
+      // move past the single column characters
+      let single_column_count = char_index - byte_index;
+      if display_index + single_column_count > target_index {
+        return Ok(byte_index + target_index - display_index);
+      }
+      display_index += single_column_count;
+      byte_index = char_index;
+
+      // skip over zero-width characters so the column resolves to the next character
+      if char_width > 0 {
+        if display_index == target_index {
+          return Ok(byte_index);
+        } else if display_index + char_width > target_index {
+          return match snap {
+            DisplaySnap::Left => Ok(byte_index),
+            DisplaySnap::Right => Ok(byte_index + char_length),
+            DisplaySnap::Error => Err(TextLinesError::DisplayColumnInsideChar {
+              line_number: line_and_column.line_number,
+              column_number: line_and_column.column_number,
+            }),
+          };
+        }
+      }
+      display_index += char_width;
+      byte_index += char_length;
+    }
+
+    // fallback gracefully to the end index of the line when the column goes off
+    Ok(std::cmp::min(
+      byte_index + target_index - display_index,
+      line.end_index,
+    ))
+  }
+
+  /// Gets the line and column range of the provided byte range.
+  pub fn line_and_column_range(&self, range: impl Into<ByteRange>) -> LineAndColumnRange {
+    self.line_and_column_range_with_encoding(range, ColumnEncoding::Utf32)
+  }
+
+  /// Fallible version of [`line_and_column_range`](Self::line_and_column_range).
+  pub fn try_line_and_column_range(
+    &self,
+    range: impl Into<ByteRange>,
+  ) -> Result<LineAndColumnRange, TextLinesError> {
+    self.try_line_and_column_range_with_encoding(range, ColumnEncoding::Utf32)
+  }
+
+  /// Gets the line and column range of the provided byte range where the
+  /// columns are measured in the provided encoding.
+  pub fn line_and_column_range_with_encoding(
+    &self,
+    range: impl Into<ByteRange>,
+    encoding: ColumnEncoding,
+  ) -> LineAndColumnRange {
+    unwrap_or_panic(self.try_line_and_column_range_with_encoding(range, encoding))
+  }
+
+  /// Fallible version of [`line_and_column_range_with_encoding`](Self::line_and_column_range_with_encoding).
+  pub fn try_line_and_column_range_with_encoding(
+    &self,
+    range: impl Into<ByteRange>,
+    encoding: ColumnEncoding,
+  ) -> Result<LineAndColumnRange, TextLinesError> {
+    let range = validate_range(range.into())?;
+    Ok(LineAndColumnRange {
+      start: self.try_line_and_column_index_with_encoding(range.start, encoding)?,
+      end: self.try_line_and_column_index_with_encoding(range.end, encoding)?,
+    })
+  }
+
+  /// Gets the byte range of the provided line and column range.
+  pub fn byte_range(&self, range: LineAndColumnRange) -> ByteRange {
+    self.byte_range_with_encoding(range, ColumnEncoding::Utf32)
+  }
+
+  /// Fallible version of [`byte_range`](Self::byte_range).
+  pub fn try_byte_range(&self, range: LineAndColumnRange) -> Result<ByteRange, TextLinesError> {
+    self.try_byte_range_with_encoding(range, ColumnEncoding::Utf32)
+  }
+
+  /// Gets the byte range of the provided line and column range where the
+  /// columns are measured in the provided encoding.
+  pub fn byte_range_with_encoding(
+    &self,
+    range: LineAndColumnRange,
+    encoding: ColumnEncoding,
+  ) -> ByteRange {
+    unwrap_or_panic(self.try_byte_range_with_encoding(range, encoding))
+  }
+
+  /// Fallible version of [`byte_range_with_encoding`](Self::byte_range_with_encoding).
+  pub fn try_byte_range_with_encoding(
+    &self,
+    range: LineAndColumnRange,
+    encoding: ColumnEncoding,
+  ) -> Result<ByteRange, TextLinesError> {
+    validate_range(ByteRange {
+      start: self.try_byte_index_with_encoding(range.start, encoding)?,
+      end: self.try_byte_index_with_encoding(range.end, encoding)?,
+    })
+  }
+
+  /// Gets the display range of the provided byte range based on the
+  /// indentation width.
+  pub fn display_range(&self, range: impl Into<ByteRange>) -> DisplayRange {
+    unwrap_or_panic(self.try_display_range(range))
+  }
+
+  /// Fallible version of [`display_range`](Self::display_range).
+  pub fn try_display_range(
+    &self,
+    range: impl Into<ByteRange>,
+  ) -> Result<DisplayRange, TextLinesError> {
+    let range = validate_range(range.into())?;
+    Ok(DisplayRange {
+      start: self.try_line_and_column_display(range.start)?,
+      end: self.try_line_and_column_display(range.end)?,
+    })
+  }
+
+  /// Gets the byte range of the provided display range based on the
+  /// indentation width.
+  ///
+  /// The range is expanded to contain any tab or wide character that a
+  /// column is inside of.
+  pub fn byte_range_from_display(&self, range: DisplayRange) -> ByteRange {
+    unwrap_or_panic(self.try_byte_range_from_display(range))
+  }
+
+  /// Fallible version of [`byte_range_from_display`](Self::byte_range_from_display).
+  pub fn try_byte_range_from_display(
+    &self,
+    range: DisplayRange,
+  ) -> Result<ByteRange, TextLinesError> {
+    validate_range(ByteRange {
+      start: self.try_byte_index_from_display(range.start, DisplaySnap::Left)?,
+      end: self.try_byte_index_from_display(range.end, DisplaySnap::Right)?,
+    })
+  }
+
+  /// Splits the provided byte range into the segments of it on each line.
+  ///
+  /// A range that ends at the start of a line after its first line ends on
+  /// the previous line. An empty range has a single segment.
+  pub fn line_segments(&self, range: impl Into<ByteRange>) -> Vec<LineSegment> {
+    unwrap_or_panic(self.try_line_segments(range))
+  }
+
+  /// Fallible version of [`line_segments`](Self::line_segments).
+  pub fn try_line_segments(
+    &self,
+    range: impl Into<ByteRange>,
+  ) -> Result<Vec<LineSegment>, TextLinesError> {
+    let range = validate_range(range.into())?;
+    let start_line_index = self.try_line_index(range.start)?;
+    let mut end_line_index = self.try_line_index(range.end)?;
+    if end_line_index > start_line_index && range.end <= self.lines.line_start(end_line_index) {
+      end_line_index -= 1; // don't include an empty segment for the newline
+    }
+
+    let mut segments = Vec::with_capacity(end_line_index - start_line_index + 1);
+    for line_index in start_line_index..=end_line_index {
+      let line = self.lines.line(line_index);
+      let clamp = |byte_index: usize| byte_index.clamp(line.start_index, line.end_index);
+      let (start, end) = (clamp(range.start), clamp(range.end));
+      let column_index =
+        |byte_index| line.column_index(byte_index, ColumnMeasure::Encoding(ColumnEncoding::Utf32));
+      let display_index = |byte_index| {
+        line.display_index(
+          byte_index,
+          ColumnMeasure::DisplayWidth,
+          self.options.indent_width,
+          &self.options,
+        )
+      };
+      let starts_here = line_index == start_line_index;
+      let ends_here = line_index == end_line_index;
+      segments.push(LineSegment {
+        line_index,
+        byte_range: ByteRange { start, end },
+        columns: column_index(start)..column_index(end),
+        display_columns: display_index(start)..display_index(end),
+        starts_here,
+        ends_here,
+        continues: !starts_here && !ends_here,
+      });
+    }
+    Ok(segments)
+  }
+
+  /// Gets a view of the line of the provided byte index in `text` that's
+  /// truncated around it to fit within the maximum display width.
+  ///
+  /// Tabs are expanded to spaces and `…` markers take the place of the left
+  /// out text, which never splits a character or tab. The display index of
+  /// the byte index in the view is provided so a caret can be drawn under it.
+  ///
+  /// When the line needs to be truncated, the maximum width must be at least
+  /// the display width of the character at the byte index plus two for the
+  /// markers so that the character is always in the view.
+  pub fn truncated_line(&self, text: &str, byte_index: usize, max_width: usize) -> TruncatedLine {
+    unwrap_or_panic(self.try_truncated_line(text, byte_index, max_width))
+  }
+
+  /// Fallible version of [`truncated_line`](Self::truncated_line).
+  pub fn try_truncated_line(
+    &self,
+    text: &str,
+    byte_index: usize,
+    max_width: usize,
+  ) -> Result<TruncatedLine, TextLinesError> {
+    if text.len() != self.text_length() {
+      return Err(TextLinesError::TextLengthMismatch {
+        expected: self.text_length(),
+        actual: text.len(),
+      });
+    }
+    let line_index = self.try_line_index(byte_index)?;
+    let line = self.lines.line(line_index);
+    let display_index = |byte_index| {
+      line.display_index(
+        byte_index,
+        ColumnMeasure::DisplayWidth,
+        self.options.indent_width,
+        &self.options,
+      )
+    };
+    let target_display_index = display_index(byte_index);
+    let target_char_start = char_start(text, byte_index);
+    let target_char_end = match text[target_char_start..].chars().next() {
+      Some(c) if byte_index < line.end_index => target_char_start + c.len_utf8(),
+      _ => byte_index,
+    };
+    let target_display_end = display_index(target_char_end);
+    let line_width = display_index(line.end_index);
+
+    let (start, end) = if line_width <= max_width {
+      (line.start_index, line.end_index)
+    } else {
+      let min_width = target_display_end - target_display_index + 2;
+      if max_width < min_width {
+        return Err(TextLinesError::MaxWidthTooSmall {
+          max_width,
+          min_width,
+        });
+      }
+      // leave room for a marker on each side
+      let content_width = max_width - 2;
+      let mut window_start = std::cmp::min(
+        target_display_index.saturating_sub(content_width / 2),
+        line_width - content_width,
+      );
+      // include all of the target character when it's near the end
+      window_start = std::cmp::max(
+        window_start,
+        target_display_end.saturating_sub(content_width),
+      );
+      let mut window_end = window_start + content_width;
+      // use the room of a marker that isn't necessary
+      if window_start == 0 {
+        window_end += 1;
+      } else if window_end == line_width {
+        window_start -= 1;
+      }
+      let to_byte_index = |display_index: usize, snap| {
+        self.try_byte_index_from_display(
+          LineAndColumnDisplay {
+            line_number: line_index + 1,
+            column_number: display_index + 1,
+          },
+          snap,
+        )
+      };
+      // only include the characters that are entirely within the window,
+      // which may leave out a zero-width target character when snapping
+      (
+        std::cmp::min(
+          to_byte_index(window_start, DisplaySnap::Right)?,
+          target_char_start,
+        ),
+        std::cmp::max(
+          to_byte_index(window_end, DisplaySnap::Left)?,
+          target_char_end,
+        ),
+      )
+    };
+
+    let is_start_truncated = start > line.start_index;
+    let mut view_text = String::new();
+    if is_start_truncated {
+      view_text.push('…');
+    }
+    view_text.push_str(&self.expand_tabs(text, start..end));
+    if end < line.end_index {
+      view_text.push('…');
+    }
+    Ok(TruncatedLine {
+      text: view_text,
+      byte_range: ByteRange { start, end },
+      display_index: usize::from(is_start_truncated)
+        + target_display_index.saturating_sub(display_index(start)),
+    })
+  }
+
+  /// Gets the text in the byte range of a line with the tabs replaced by
+  /// the number of spaces they take up in the display columns.
+  pub(crate) fn expand_tabs(&self, text: &str, range: Range<usize>) -> String {
+    let range_text = &text[range.start..range.end];
+    let mut expanded = String::with_capacity(range_text.len());
+    for (index, c) in range_text.char_indices() {
+      if c == '\t' {
+        let byte_index = range.start + index;
+        let width = self.line_and_column_display(byte_index + 1).column_number
+          - self.line_and_column_display(byte_index).column_number;
+        expanded.push_str(&" ".repeat(width));
+      } else {
+        expanded.push(c);
+      }
+    }
+    expanded
+  }
+
+  fn get_line_and_column_display(
+    &self,
+    byte_index: usize,
+    indent_width: usize,
+    measure: ColumnMeasure,
+  ) -> Result<LineAndColumnDisplay, TextLinesError> {
+    let line_index = self.try_line_index(byte_index)?;
+    let line = self.lines.line(line_index);
+    let column_index = line.display_index(byte_index, measure, indent_width, &self.options);
+
+    Ok(LineAndColumnDisplay {
+      line_number: line_index + 1,
+      column_number: column_index + 1,
+    })
+  }
+
+  fn get_line(&self, line_index: usize) -> Result<TextLine<'_>, TextLinesError> {
+    if line_index < self.lines.len() {
+      Ok(self.lines.line(line_index))
+    } else {
+      Err(TextLinesError::LineIndexOutOfRange {
+        line_index,
+        lines_count: self.lines.len(),
+      })
+    }
+  }
+
+  fn validate_byte_index(&self, byte_index: usize) -> Result<(), TextLinesError> {
+    if byte_index > self.text_length() {
+      Err(TextLinesError::ByteIndexOutOfRange {
+        byte_index,
+        text_length: self.text_length(),
+      })
+    } else {
+      Ok(())
+    }
+  }
+}
+
+/// Gets the start of the character at the byte index.
+fn char_start(text: &str, byte_index: usize) -> usize {
+  (0..=byte_index)
+    .rev()
+    .find(|index| text.is_char_boundary(*index))
+    .unwrap()
+}
+
+fn validate_range(range: ByteRange) -> Result<ByteRange, TextLinesError> {
+  if range.start > range.end {
+    Err(TextLinesError::InvalidRange {
+      start: range.start,
+      end: range.end,
+    })
+  } else {
+    Ok(range)
+  }
+}
+
+fn last_grapheme(text: &str) -> Vec<u8> {
+  let last_grapheme = text.graphemes(true).next_back().unwrap_or("");
+  last_grapheme.as_bytes().to_vec()
+}
+
+/// Gets the last grapheme cluster of bytes that may not be valid UTF-8,
+/// where an invalid sequence is a grapheme cluster on its own.
+fn last_grapheme_of_bytes(bytes: &[u8]) -> Vec<u8> {
+  match utf8_chunks(bytes).last() {
+    Some((_, invalid_length)) if invalid_length > 0 => {
+      bytes[bytes.len() - invalid_length..].to_vec()
+    }
+    Some((valid_text, _)) => last_grapheme(valid_text),
+    None => Vec::new(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use unicode_width::UnicodeWidthChar;
+
+  use super::*;
+  use crate::test_helpers::for_each_oracle;
+
+  #[test]
+  fn line_and_column_index() {
+    let text = "12\n3\r\n4\n5";
+    let info = TextLines::new(text);
+    assert_line_and_col_index(&info, 0, 0, 0); // 1
     assert_line_and_col_index(&info, 1, 0, 1); // 2
     assert_line_and_col_index(&info, 2, 0, 2); // \n
     assert_line_and_col_index(&info, 3, 1, 0); // 3
@@ -474,6 +1447,185 @@ mod tests {
     );
   }
 
+  #[test]
+  fn line_and_column_display_with_encoding() {
+    let text = "\t😀1";
+    let info = TextLines::new(text);
+    let display = |byte_index, encoding| {
+      info
+        .line_and_column_display_with_encoding(byte_index, encoding)
+        .column_number
+    };
+    assert_eq!(display(5, ColumnEncoding::Utf8), 9); // 1
+    assert_eq!(display(5, ColumnEncoding::Utf16), 7);
+    assert_eq!(display(5, ColumnEncoding::Utf32), 6);
+  }
+
+  #[test]
+  fn line_and_column_display_wide_chars() {
+    let text = "日本\te\u{301}x\u{FF01}😀\u{200B}1";
+    let info = TextLines::new(text);
+    assert_line_and_col_display(&info, 0, 1, 1); // 日
+    assert_line_and_col_display(&info, 3, 1, 3); // 本
+    assert_line_and_col_display(&info, 6, 1, 5); // \t
+    assert_line_and_col_display(&info, 7, 1, 9); // e
+    assert_line_and_col_display(&info, 8, 1, 10); // combining acute accent
+    assert_line_and_col_display(&info, 10, 1, 10); // x
+    assert_line_and_col_display(&info, 11, 1, 11); // fullwidth !
+    assert_line_and_col_display(&info, 12, 1, 11); // middle of fullwidth !
+    assert_line_and_col_display(&info, 14, 1, 13); // 😀
+    assert_line_and_col_display(&info, 18, 1, 15); // zero width space
+    assert_line_and_col_display(&info, 21, 1, 15); // 1
+    assert_line_and_col_display(&info, 22, 1, 16); // <EOF>
+  }
+
+  #[test]
+  fn line_and_column_display_wide_chars_tab_stops() {
+    let text = "日\t1";
+    let info = TextLines::with_options(
+      text,
+      TextLinesOptions {
+        tab_display: TabDisplay::TabStops,
+        ..Default::default()
+      },
+    );
+    assert_line_and_col_display(&info, 3, 1, 3); // \t
+    assert_line_and_col_display(&info, 4, 1, 5); // 1
+  }
+
+  #[test]
+  fn line_and_column_display_tab_stops() {
+    let text = "ab\tc\n\t 3\t4\n\t\tβ\t5";
+    let info = TextLines::with_options(
+      text,
+      TextLinesOptions {
+        tab_display: TabDisplay::TabStops,
+        ..Default::default()
+      },
+    );
+    assert_line_and_col_display(&info, 2, 1, 3); // \t
+    assert_line_and_col_display(&info, 3, 1, 5); // c
+    assert_line_and_col_display(&info, 5, 2, 1); // \t
+    assert_line_and_col_display(&info, 6, 2, 5); // <space>
+    assert_line_and_col_display(&info, 7, 2, 6); // 3
+    assert_line_and_col_display(&info, 8, 2, 7); // \t
+    assert_line_and_col_display(&info, 9, 2, 9); // 4
+    assert_line_and_col_display(&info, 13, 3, 9); // β
+    assert_line_and_col_display(&info, 15, 3, 10); // \t
+    assert_line_and_col_display(&info, 16, 3, 13); // 5
+    assert_eq!(
+      info
+        .line_and_column_display_with_indent_width(16, 2)
+        .column_number,
+      7
+    );
+    assert_eq!(
+      info
+        .line_and_column_display_with_encoding(16, ColumnEncoding::Utf8)
+        .column_number,
+      13
+    );
+  }
+
+  #[test]
+  fn byte_index_from_display() {
+    let text = "ab\tc\n日本\te\u{301}x\r\n";
+    let info = TextLines::new(text);
+    assert_byte_index_from_display(&info, 1, 1, DisplaySnap::Error, Some(0)); // a
+    assert_byte_index_from_display(&info, 1, 3, DisplaySnap::Error, Some(2)); // \t
+    assert_byte_index_from_display(&info, 1, 4, DisplaySnap::Left, Some(2)); // inside \t
+    assert_byte_index_from_display(&info, 1, 6, DisplaySnap::Right, Some(3)); // inside \t
+    assert_byte_index_from_display(&info, 1, 5, DisplaySnap::Error, None); // inside \t
+    assert_byte_index_from_display(&info, 1, 7, DisplaySnap::Error, Some(3)); // c
+    assert_byte_index_from_display(&info, 1, 8, DisplaySnap::Error, Some(4)); // \n
+    assert_byte_index_from_display(&info, 1, 20, DisplaySnap::Error, Some(4)); // passed the \n
+    assert_byte_index_from_display(&info, 2, 1, DisplaySnap::Error, Some(5)); // 日
+    assert_byte_index_from_display(&info, 2, 2, DisplaySnap::Left, Some(5)); // inside 日
+    assert_byte_index_from_display(&info, 2, 2, DisplaySnap::Right, Some(8)); // inside 日
+    assert_byte_index_from_display(&info, 2, 2, DisplaySnap::Error, None); // inside 日
+    assert_byte_index_from_display(&info, 2, 3, DisplaySnap::Error, Some(8)); // 本
+    assert_byte_index_from_display(&info, 2, 5, DisplaySnap::Error, Some(11)); // \t
+    assert_byte_index_from_display(&info, 2, 9, DisplaySnap::Error, Some(12)); // e
+    assert_byte_index_from_display(&info, 2, 10, DisplaySnap::Error, Some(15)); // x
+    assert_byte_index_from_display(&info, 2, 11, DisplaySnap::Error, Some(16)); // \r
+    assert_byte_index_from_display(&info, 2, 12, DisplaySnap::Error, Some(16)); // \n
+    assert_byte_index_from_display(&info, 3, 1, DisplaySnap::Error, Some(18)); // <EOF>
+  }
+
+  #[test]
+  fn byte_index_from_display_tab_stops() {
+    let text = "\u{FEFF}ab\tc\t";
+    let info = TextLines::with_options(
+      text,
+      TextLinesOptions {
+        tab_display: TabDisplay::TabStops,
+        ..Default::default()
+      },
+    );
+    assert_byte_index_from_display(&info, 1, 1, DisplaySnap::Error, Some(3)); // a
+    assert_byte_index_from_display(&info, 1, 3, DisplaySnap::Error, Some(5)); // \t
+    assert_byte_index_from_display(&info, 1, 4, DisplaySnap::Right, Some(6)); // inside \t
+    assert_byte_index_from_display(&info, 1, 5, DisplaySnap::Error, Some(6)); // c
+    assert_byte_index_from_display(&info, 1, 6, DisplaySnap::Error, Some(7)); // \t
+    assert_byte_index_from_display(&info, 1, 8, DisplaySnap::Left, Some(7)); // inside \t
+    assert_byte_index_from_display(&info, 1, 9, DisplaySnap::Error, Some(8)); // <EOF>
+  }
+
+  #[test]
+  fn byte_index_from_display_round_trip() {
+    let text = "\t日 \tβ\t😀x\n\t\t1";
+    for tab_display in [TabDisplay::FixedWidth, TabDisplay::TabStops] {
+      let info = TextLines::with_options(
+        text,
+        TextLinesOptions {
+          tab_display,
+          ..Default::default()
+        },
+      );
+      for (byte_index, _) in text.char_indices() {
+        let display = info.line_and_column_display(byte_index);
+        assert_eq!(
+          info.byte_index_from_display(display, DisplaySnap::Error),
+          Some(byte_index)
+        );
+      }
+    }
+  }
+
+  #[test]
+  #[should_panic(
+    expected = "The specified line number 1 and column number 0 must both be 1 or greater."
+  )]
+  fn byte_index_from_display_zero_column() {
+    let info = TextLines::new("test");
+    info.byte_index_from_display(
+      LineAndColumnDisplay {
+        line_number: 1,
+        column_number: 0,
+      },
+      DisplaySnap::Left,
+    );
+  }
+
+  fn assert_byte_index_from_display(
+    info: &TextLines,
+    line_number: usize,
+    column_number: usize,
+    snap: DisplaySnap,
+    byte_index: Option<usize>,
+  ) {
+    assert_eq!(
+      info.byte_index_from_display(
+        LineAndColumnDisplay {
+          line_number,
+          column_number,
+        },
+        snap
+      ),
+      byte_index
+    );
+  }
+
   #[test]
   #[should_panic(expected = "The specified byte index 5 was greater than the text length of 4.")]
   fn line_and_column_index_panic_greater_than() {
@@ -481,6 +1633,124 @@ mod tests {
     info.line_and_column_index(5);
   }
 
+  #[test]
+  #[should_panic(
+    expected = "The specified line index 2 was greater or equal to the number of lines of 2."
+  )]
+  fn byte_index_panic_line_index() {
+    let info = TextLines::new("1\n2");
+    info.byte_index(LineAndColumnIndex {
+      line_index: 2,
+      column_index: 0,
+    });
+  }
+
+  #[test]
+  fn try_methods() {
+    let mut info = TextLines::new("1\n2β");
+    let byte_index_error = Err(TextLinesError::ByteIndexOutOfRange {
+      byte_index: 6,
+      text_length: 5,
+    });
+    let line_index_error = TextLinesError::LineIndexOutOfRange {
+      line_index: 2,
+      lines_count: 2,
+    };
+    assert_eq!(info.try_line_index(5), Ok(1));
+    assert_eq!(info.try_line_index(6), byte_index_error);
+    assert_eq!(info.try_line_start(2), Err(line_index_error.clone()));
+    assert_eq!(info.try_line_end(1), Ok(5));
+    assert_eq!(info.try_line_range(2), Err(line_index_error.clone()));
+    assert_eq!(
+      info.try_byte_index(LineAndColumnIndex {
+        line_index: 2,
+        column_index: 0,
+      }),
+      Err(line_index_error.clone())
+    );
+    assert_eq!(
+      info.try_line_and_column_index(6),
+      Err(TextLinesError::ByteIndexOutOfRange {
+        byte_index: 6,
+        text_length: 5,
+      })
+    );
+    assert_eq!(
+      info.try_line_and_column_display(6),
+      Err(TextLinesError::ByteIndexOutOfRange {
+        byte_index: 6,
+        text_length: 5,
+      })
+    );
+    assert_eq!(
+      info.try_byte_index_from_display(
+        LineAndColumnDisplay {
+          line_number: 3,
+          column_number: 1,
+        },
+        DisplaySnap::Left
+      ),
+      Err(line_index_error)
+    );
+    assert_eq!(
+      info.try_apply_edit(Range { start: 1, end: 0 }, "", "1\n2β"),
+      Err(TextLinesError::InvalidRange { start: 1, end: 0 })
+    );
+    assert_eq!(
+      info.try_apply_edit(0..1, "", "1\n2β"),
+      Err(TextLinesError::TextLengthMismatch {
+        expected: 4,
+        actual: 5,
+      })
+    );
+    assert_eq!(
+      info.try_apply_edit(0..1, "β", "😀\n2"),
+      Err(TextLinesError::NotCharBoundary { byte_index: 2 })
+    );
+    assert_eq!(
+      info.try_apply_edit(0..1, "a", "b\n2β"),
+      Err(TextLinesError::EditTextMismatch { start: 0, end: 1 })
+    );
+    for (line_number, column_number) in [(0, 0), (0, 1), (1, 0)] {
+      assert_eq!(
+        info.try_byte_index_from_display(
+          LineAndColumnDisplay {
+            line_number,
+            column_number,
+          },
+          DisplaySnap::Left
+        ),
+        Err(TextLinesError::InvalidLineAndColumnDisplay {
+          line_number,
+          column_number,
+        })
+      );
+    }
+    assert_eq!(info, TextLines::new("1\n2β"));
+    assert_eq!(info.try_apply_edit(0..1, "β", "β\n2β"), Ok(()));
+    assert_eq!(info, TextLines::new("β\n2β"));
+  }
+
+  #[test]
+  fn try_byte_index_from_display_inside_char() {
+    let info = TextLines::new("\t1");
+    let display = LineAndColumnDisplay {
+      line_number: 1,
+      column_number: 2,
+    };
+    assert_eq!(
+      info.try_byte_index_from_display(display, DisplaySnap::Error),
+      Err(TextLinesError::DisplayColumnInsideChar {
+        line_number: 1,
+        column_number: 2,
+      })
+    );
+    assert_eq!(
+      info.try_byte_index_from_display(display, DisplaySnap::Right),
+      Ok(1)
+    );
+  }
+
   #[test]
   fn line_start() {
     let text = "12\n3\r\n4\n5";
@@ -587,6 +1857,218 @@ mod tests {
     );
   }
 
+  #[test]
+  fn byte_index_bom_multi_byte_chars() {
+    let text = "\u{FEFF}β1";
+    let info = TextLines::new(text);
+    assert_byte_index(&info, 0, 0, 3); // β
+    assert_byte_index(&info, 0, 1, 5); // 1
+    assert_byte_index(&info, 0, 2, 6); // <EOF>
+  }
+
+  #[test]
+  fn line_and_utf16_column_index() {
+    let text = "a😀β\r\n\u{1F469}b";
+    let info = TextLines::new(text);
+    assert_line_and_utf16_col_index(&info, 0, 0, 0); // a
+    assert_line_and_utf16_col_index(&info, 1, 0, 1); // first 😀 index
+    assert_line_and_utf16_col_index(&info, 3, 0, 1); // third 😀 index
+    assert_line_and_utf16_col_index(&info, 5, 0, 3); // first β index
+    assert_line_and_utf16_col_index(&info, 6, 0, 3); // second β index
+    assert_line_and_utf16_col_index(&info, 7, 0, 4); // \r
+    assert_line_and_utf16_col_index(&info, 8, 0, 5); // \n
+    assert_line_and_utf16_col_index(&info, 9, 1, 0); // 👩
+    assert_line_and_utf16_col_index(&info, 13, 1, 2); // b
+    assert_line_and_utf16_col_index(&info, 14, 1, 3); // <EOF>
+  }
+
+  #[test]
+  fn line_and_utf16_column_index_bom() {
+    let text = "\u{FEFF}😀1";
+    let info = TextLines::new(text);
+    assert_line_and_utf16_col_index(&info, 0, 0, 0); // BOM
+    assert_line_and_utf16_col_index(&info, 3, 0, 0); // 😀
+    assert_line_and_utf16_col_index(&info, 7, 0, 2); // 1
+    assert_line_and_utf16_col_index(&info, 8, 0, 3); // <EOF>
+  }
+
+  fn assert_line_and_utf16_col_index(
+    info: &TextLines,
+    byte_index: usize,
+    line_index: usize,
+    column_index: usize,
+  ) {
+    assert_eq!(
+      info.line_and_utf16_column_index(byte_index),
+      LineAndColumnIndex {
+        line_index,
+        column_index,
+      }
+    );
+  }
+
+  #[test]
+  fn byte_index_from_utf16() {
+    let text = "a😀β\r\n\u{1F469}b";
+    let info = TextLines::new(text);
+    assert_byte_index_from_utf16(&info, 0, 0, 0); // a
+    assert_byte_index_from_utf16(&info, 0, 1, 1); // 😀
+    assert_byte_index_from_utf16(&info, 0, 2, 1); // middle of 😀 surrogate pair
+    assert_byte_index_from_utf16(&info, 0, 3, 5); // β
+    assert_byte_index_from_utf16(&info, 0, 4, 7); // \r
+    assert_byte_index_from_utf16(&info, 0, 5, 7); // passed the \r\n
+    assert_byte_index_from_utf16(&info, 1, 0, 9); // 👩
+    assert_byte_index_from_utf16(&info, 1, 2, 13); // b
+    assert_byte_index_from_utf16(&info, 1, 3, 14); // <EOF>
+    assert_byte_index_from_utf16(&info, 1, 4, 14); // passed the <EOF>
+  }
+
+  #[test]
+  fn byte_index_from_utf16_bom() {
+    let text = "\u{FEFF}😀1";
+    let info = TextLines::new(text);
+    assert_byte_index_from_utf16(&info, 0, 0, 3); // 😀
+    assert_byte_index_from_utf16(&info, 0, 2, 7); // 1
+    assert_byte_index_from_utf16(&info, 0, 3, 8); // <EOF>
+  }
+
+  fn assert_byte_index_from_utf16(
+    info: &TextLines,
+    line_index: usize,
+    column_index: usize,
+    byte_index: usize,
+  ) {
+    assert_eq!(
+      info.byte_index_from_utf16(LineAndColumnIndex {
+        line_index,
+        column_index,
+      }),
+      byte_index,
+    );
+  }
+
+  #[test]
+  fn line_and_column_index_with_encoding() {
+    let text = "a😀β\nb";
+    let info = TextLines::new(text);
+    assert_line_and_col_index_with_encoding(&info, 5, ColumnEncoding::Utf8, 0, 5); // β
+    assert_line_and_col_index_with_encoding(&info, 5, ColumnEncoding::Utf16, 0, 3);
+    assert_line_and_col_index_with_encoding(&info, 5, ColumnEncoding::Utf32, 0, 2);
+    assert_line_and_col_index_with_encoding(&info, 6, ColumnEncoding::Utf8, 0, 5); // second β index
+    assert_line_and_col_index_with_encoding(&info, 7, ColumnEncoding::Utf8, 0, 7); // \n
+    assert_line_and_col_index_with_encoding(&info, 7, ColumnEncoding::Utf16, 0, 4);
+    assert_line_and_col_index_with_encoding(&info, 7, ColumnEncoding::Utf32, 0, 3);
+    // <EOF>
+    assert_line_and_col_index_with_encoding(&info, 9, ColumnEncoding::Utf8, 1, 1);
+  }
+
+  fn assert_line_and_col_index_with_encoding(
+    info: &TextLines,
+    byte_index: usize,
+    encoding: ColumnEncoding,
+    line_index: usize,
+    column_index: usize,
+  ) {
+    assert_eq!(
+      info.line_and_column_index_with_encoding(byte_index, encoding),
+      LineAndColumnIndex {
+        line_index,
+        column_index,
+      }
+    );
+  }
+
+  #[test]
+  fn byte_index_with_encoding() {
+    let text = "a😀β\nb";
+    let info = TextLines::new(text);
+    assert_byte_index_with_encoding(&info, 0, 5, ColumnEncoding::Utf8, 5); // β
+    assert_byte_index_with_encoding(&info, 0, 3, ColumnEncoding::Utf16, 5);
+    assert_byte_index_with_encoding(&info, 0, 2, ColumnEncoding::Utf32, 5);
+    assert_byte_index_with_encoding(&info, 0, 6, ColumnEncoding::Utf8, 5); // middle of β
+    assert_byte_index_with_encoding(&info, 0, 8, ColumnEncoding::Utf8, 7); // passed the \n
+    assert_byte_index_with_encoding(&info, 1, 1, ColumnEncoding::Utf8, 9); // <EOF>
+  }
+
+  fn assert_byte_index_with_encoding(
+    info: &TextLines,
+    line_index: usize,
+    column_index: usize,
+    encoding: ColumnEncoding,
+    byte_index: usize,
+  ) {
+    assert_eq!(
+      info.byte_index_with_encoding(
+        LineAndColumnIndex {
+          line_index,
+          column_index,
+        },
+        encoding
+      ),
+      byte_index,
+    );
+  }
+
+  #[test]
+  fn grapheme_columns() {
+    let text = "a🇯🇵e\u{301}👩\u{200D}👧b\n\u{301}1";
+    let info = TextLines::new(text);
+    let encoding = ColumnEncoding::Graphemes;
+    assert_line_and_col_index_with_encoding(&info, 0, encoding, 0, 0); // a
+    assert_line_and_col_index_with_encoding(&info, 1, encoding, 0, 1); // flag
+    assert_line_and_col_index_with_encoding(&info, 5, encoding, 0, 1); // second regional indicator
+    assert_line_and_col_index_with_encoding(&info, 9, encoding, 0, 2); // e
+    assert_line_and_col_index_with_encoding(&info, 10, encoding, 0, 2); // combining acute accent
+    assert_line_and_col_index_with_encoding(&info, 12, encoding, 0, 3); // family
+    assert_line_and_col_index_with_encoding(&info, 19, encoding, 0, 3); // last person in family
+    assert_line_and_col_index_with_encoding(&info, 23, encoding, 0, 4); // b
+    assert_line_and_col_index_with_encoding(&info, 24, encoding, 0, 5); // \n
+    assert_line_and_col_index_with_encoding(&info, 25, encoding, 1, 0); // lone combining acute accent
+    assert_line_and_col_index_with_encoding(&info, 27, encoding, 1, 1); // 1
+    assert_line_and_col_index_with_encoding(&info, 28, encoding, 1, 2); // <EOF>
+
+    assert_byte_index_with_encoding(&info, 0, 0, encoding, 0); // a
+    assert_byte_index_with_encoding(&info, 0, 1, encoding, 1); // flag
+    assert_byte_index_with_encoding(&info, 0, 2, encoding, 9); // e
+    assert_byte_index_with_encoding(&info, 0, 3, encoding, 12); // family
+    assert_byte_index_with_encoding(&info, 0, 4, encoding, 23); // b
+    assert_byte_index_with_encoding(&info, 0, 5, encoding, 24); // \n
+    assert_byte_index_with_encoding(&info, 0, 6, encoding, 24); // passed the \n
+    assert_byte_index_with_encoding(&info, 1, 0, encoding, 25); // lone combining acute accent
+    assert_byte_index_with_encoding(&info, 1, 2, encoding, 28); // <EOF>
+  }
+
+  #[test]
+  fn grapheme_columns_round_trip() {
+    for text in [
+      "\u{FEFF}\tx\u{308}\u{301}🏳\u{FE0F}\u{200D}🌈\t한국어\r\n🇺🇸🇨🇦",
+      // clusters that start or end with an ASCII character
+      "ab\u{600}12 e\u{301}a\u{301}\u{600}\u{600}x\u{301}y",
+      "\u{301}a \u{600}",
+      "é\u{301}a🇺🇸a🇨🇦🇺\u{2028}\u{301}",
+    ] {
+      let info = TextLines::new(text);
+      for line_index in 0..info.lines_count() {
+        let (start, end) = info.line_range(line_index);
+        let graphemes = text[start..end].grapheme_indices(true);
+        for (column_index, (byte_index, _)) in graphemes.enumerate() {
+          let line_and_column = LineAndColumnIndex {
+            line_index,
+            column_index,
+          };
+          assert_eq!(
+            info.line_and_column_index_with_encoding(start + byte_index, ColumnEncoding::Graphemes),
+            line_and_column
+          );
+          assert_eq!(
+            info.byte_index_with_encoding(line_and_column, ColumnEncoding::Graphemes),
+            start + byte_index
+          );
+        }
+      }
+    }
+  }
+
   #[test]
   fn byte_index_from_char_index() {
     let text = "1234\n567\r\n8\n";
@@ -675,6 +2157,787 @@ mod tests {
     assert_eq!(info.char_index(char_index), byte_index,);
   }
 
+  #[test]
+  fn line_terminators() {
+    let text = "1\n2\r\n3\r4\u{2028}5\u{2029}6\u{0B}7\u{0C}8\u{85}9";
+    assert_line_ranges(text, LineTerminators::Lf, &[(0, 1), (2, 4), (5, 23)]);
+    assert_line_ranges(text, LineTerminators::LfAndCrLf, &[(0, 1), (2, 3), (5, 23)]);
+    assert_line_ranges(
+      text,
+      LineTerminators::Universal,
+      &[(0, 1), (2, 3), (5, 6), (7, 23)],
+    );
+    assert_line_ranges(
+      text,
+      LineTerminators::EcmaScript,
+      &[(0, 1), (2, 3), (5, 6), (7, 8), (11, 12), (15, 23)],
+    );
+    assert_line_ranges(
+      text,
+      LineTerminators::Unicode,
+      &[
+        (0, 1),
+        (2, 3),
+        (5, 6),
+        (7, 8),
+        (11, 12),
+        (15, 16),
+        (17, 18),
+        (19, 20),
+        (22, 23),
+      ],
+    );
+  }
+
+  #[test]
+  fn line_terminators_trailing_cr() {
+    assert_line_ranges("1\r", LineTerminators::LfAndCrLf, &[(0, 2)]);
+    assert_line_ranges("1\r", LineTerminators::Universal, &[(0, 1), (2, 2)]);
+    assert_line_ranges(
+      "\r\r\n",
+      LineTerminators::Universal,
+      &[(0, 0), (1, 1), (3, 3)],
+    );
+  }
+
+  fn assert_line_ranges(
+    text: &str,
+    line_terminators: LineTerminators,
+    expected: &[(usize, usize)],
+  ) {
+    let info = TextLines::with_options(
+      text,
+      TextLinesOptions {
+        line_terminators,
+        ..Default::default()
+      },
+    );
+    let ranges = (0..info.lines_count())
+      .map(|line_index| info.line_range(line_index))
+      .collect::<Vec<_>>();
+    assert_eq!(ranges, expected);
+  }
+
+  #[test]
+  fn line_terminators_columns() {
+    let text = "β\u{2028}1\u{85}2";
+    let info = TextLines::with_options(
+      text,
+      TextLinesOptions {
+        line_terminators: LineTerminators::Unicode,
+        ..Default::default()
+      },
+    );
+    assert_line_and_col_index(&info, 2, 0, 1); // \u{2028}
+    assert_line_and_col_index(&info, 5, 1, 0); // 1
+    assert_line_and_col_index(&info, 6, 1, 1); // \u{85}
+    assert_line_and_col_index(&info, 8, 2, 0); // 2
+    assert_byte_index(&info, 0, 2, 2); // passed the \u{2028}
+    assert_byte_index(&info, 1, 1, 6); // \u{85}
+    assert_byte_index(&info, 2, 1, 9); // <EOF>
+    assert_char_index(&info, 5, 2); // 1
+    assert_char_index(&info, 8, 4); // 2
+    assert_byte_index_from_char_index(&info, 4, 8); // 2
+  }
+
+  #[test]
+  fn apply_edit() {
+    assert_apply_edit("", 0..0, "a\nb");
+    assert_apply_edit("a\nb", 0..3, "");
+    assert_apply_edit("12\n3\r\n4\n5", 4..4, "\t😀\n");
+    assert_apply_edit("12\n3\r\n4\n5", 4..5, "");
+    assert_apply_edit("12\n3\r\n4\n5", 5..5, "\r");
+    assert_apply_edit("12\n3\r\n4\n5", 3..3, "\n");
+    assert_apply_edit("12\n3\r\n4\n5", 2..3, "β");
+    assert_apply_edit("1\r\n2", 2..2, "x");
+    assert_apply_edit("1\r\n2", 1..1, "x\r");
+    assert_apply_edit("1\r", 2..2, "\n2");
+    assert_apply_edit("\u{FEFF}1\n2", 0..0, "a");
+    assert_apply_edit("\u{FEFF}1\n2", 0..3, "");
+    assert_apply_edit("1\n2", 0..0, "\u{FEFF}");
+    assert_apply_edit("β\tβ\nβ\tβ\nβ", 3..6, "\n\n");
+  }
+
+  #[test]
+  fn apply_edit_many() {
+    for line_terminators in [
+      LineTerminators::Lf,
+      LineTerminators::LfAndCrLf,
+      LineTerminators::Universal,
+      LineTerminators::Unicode,
+    ] {
+      assert_apply_edit_many(line_terminators);
+    }
+  }
+
+  fn assert_apply_edit_many(line_terminators: LineTerminators) {
+    let options = TextLinesOptions {
+      line_terminators,
+      ..Default::default()
+    };
+    let pieces = [
+      "a", "\n", "\r", "\t", "β", "😀", "\r\n", "\u{FEFF}", "\u{2028}", "\u{85}",
+    ];
+    let mut seed = 7u32;
+    let mut next = |max: usize| {
+      seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+      (seed >> 16) as usize % max
+    };
+    let mut text = String::new();
+    let mut info = TextLines::with_options(&text, options);
+    for _ in 0..500 {
+      let char_count = text.chars().count();
+      let start_char = next(char_count + 1);
+      let end_char = start_char + next(char_count - start_char + 1).min(3);
+      let byte_at = |char_index: usize| {
+        text
+          .char_indices()
+          .nth(char_index)
+          .map(|(index, _)| index)
+          .unwrap_or(text.len())
+      };
+      let range = byte_at(start_char)..byte_at(end_char);
+      let new_text = (0..next(4))
+        .map(|_| pieces[next(pieces.len())])
+        .collect::<String>();
+      text.replace_range(range.clone(), &new_text);
+      info.apply_edit(range, &new_text, &text);
+      assert_eq!(
+        info,
+        TextLines::with_options(&text, options),
+        "Text: {:?}",
+        text
+      );
+    }
+  }
+
+  fn assert_apply_edit(text: &str, range: Range<usize>, new_text: &str) {
+    let mut updated_text = text.to_string();
+    updated_text.replace_range(range.clone(), new_text);
+    let mut info = TextLines::new(text);
+    info.apply_edit(range, new_text, &updated_text);
+    assert_eq!(info, TextLines::new(&updated_text));
+  }
+
+  #[test]
+  #[should_panic(expected = "The updated text from byte index 1 to 2 did not match the new text.")]
+  fn apply_edit_new_text_mismatch() {
+    let mut info = TextLines::new("1\n2");
+    info.apply_edit(1..1, "a", "1\nb2");
+  }
+
+  #[test]
+  fn append() {
+    let texts = [
+      "",
+      "\u{FEFF}a\r\nb",
+      "ab\r\n\r\n\rc\r",
+      "\tβ\te\u{0301}\u{0302}\n中😀\t",
+      "🇺🇸🇺🇸🇺\u{2028}👨\u{200D}👩\u{200D}👧\u{85}",
+    ];
+    for text in texts {
+      for line_terminators in [
+        LineTerminators::Lf,
+        LineTerminators::LfAndCrLf,
+        LineTerminators::Universal,
+        LineTerminators::Unicode,
+      ] {
+        let options = TextLinesOptions {
+          line_terminators,
+          tab_display: TabDisplay::TabStops,
+          ..Default::default()
+        };
+        let expected = TextLines::with_options(text, options);
+        let char_boundaries = text
+          .char_indices()
+          .map(|(index, _)| index)
+          .chain(std::iter::once(text.len()))
+          .collect::<Vec<_>>();
+        for (i, first_index) in char_boundaries.iter().enumerate() {
+          for second_index in &char_boundaries[i..] {
+            let mut info = TextLines::with_options(&text[..*first_index], options);
+            info.append(&text[*first_index..*second_index]);
+            info.append(&text[*second_index..]);
+            assert_eq!(
+              info, expected,
+              "Text: {:?}, Split: {} {}, Line terminators: {:?}",
+              text, first_index, second_index, line_terminators
+            );
+          }
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn from_bytes() {
+    let bytes = b"a\xFF\xFEb\r\n\xCE\xB2\xE2\x82\n\xF0\x9F\x98\x80\xF0\x9F\tc";
+    let info = TextLines::from_bytes(bytes, Default::default());
+    assert_eq!(info.lines_count(), 3);
+    assert_eq!(info.line_range(0), (0, 4));
+    assert_eq!(info.line_range(1), (6, 10));
+    assert_eq!(info.line_range(2), (11, 19));
+    assert_eq!(
+      info.line_and_column_index(3),
+      LineAndColumnIndex {
+        line_index: 0,
+        column_index: 3,
+      }
+    );
+    assert_eq!(
+      info.line_and_column_index_with_encoding(17, ColumnEncoding::Utf16),
+      LineAndColumnIndex {
+        line_index: 2,
+        column_index: 3,
+      }
+    );
+    assert_eq!(
+      info.line_and_column_display(18),
+      LineAndColumnDisplay {
+        line_number: 3,
+        column_number: 8,
+      }
+    );
+    assert_eq!(
+      info.byte_index(LineAndColumnIndex {
+        line_index: 1,
+        column_index: 2,
+      }),
+      10
+    );
+    assert_eq!(info.char_index(19), 13);
+    assert_eq!(info.byte_index_from_char_index(7), 8);
+
+    // valid text is the same as creating from a string
+    let text = "\u{FEFF}a\r\nβ\te\u{0301}";
+    assert_eq!(
+      TextLines::from_bytes(text.as_bytes(), Default::default()),
+      TextLines::new(text)
+    );
+  }
+
+  #[test]
+  fn from_bytes_matches_lossy_text() {
+    let texts: [&[u8]; 5] = [
+      b"\xFF",
+      b"\xEF\xBB\xBF\xC0\r\n\xE2\x82",
+      b"\t\xF0\x9F\x98\t\x80\x80\n",
+      b"e\xCC\x81\xCCa\x85\r",
+      b"\xE4\xB8\xAD\xFF\xE4\xB8\xAD\xE2\x80\xA8\xF4\x90\x80\x80",
+    ];
+    for bytes in texts {
+      for line_terminators in [LineTerminators::LfAndCrLf, LineTerminators::Unicode] {
+        let options = TextLinesOptions {
+          line_terminators,
+          tab_display: TabDisplay::TabStops,
+          ..Default::default()
+        };
+        let info = TextLines::from_bytes(bytes, options);
+        let lossy_text = String::from_utf8_lossy(bytes);
+        let lossy_info = TextLines::with_options(&lossy_text, options);
+        assert_eq!(info.lines_count(), lossy_info.lines_count());
+
+        // map the start of each character to its index in the lossy text
+        let mut boundaries = Vec::new();
+        let mut index = 0;
+        let mut lossy_index = 0;
+        for (valid_text, invalid_length) in utf8_chunks(bytes) {
+          for (char_index, c) in valid_text.char_indices() {
+            boundaries.push((index + char_index, lossy_index));
+            lossy_index += c.len_utf8();
+          }
+          if invalid_length > 0 {
+            boundaries.push((index + valid_text.len(), lossy_index));
+            lossy_index += char::REPLACEMENT_CHARACTER.len_utf8();
+          }
+          index += valid_text.len() + invalid_length;
+        }
+        boundaries.push((bytes.len(), lossy_text.len()));
+
+        for (byte_index, lossy_index) in boundaries {
+          let message = format!("Bytes: {:?}, Byte index: {}", bytes, byte_index);
+          for encoding in [
+            ColumnEncoding::Utf16,
+            ColumnEncoding::Utf32,
+            ColumnEncoding::Graphemes,
+          ] {
+            let line_and_column = info.line_and_column_index_with_encoding(byte_index, encoding);
+            assert_eq!(
+              line_and_column,
+              lossy_info.line_and_column_index_with_encoding(lossy_index, encoding),
+              "{}",
+              message
+            );
+          }
+          assert_eq!(
+            info.line_and_column_display(byte_index),
+            lossy_info.line_and_column_display(lossy_index),
+            "{}",
+            message
+          );
+          assert_eq!(
+            info.char_index(byte_index),
+            lossy_info.char_index(lossy_index),
+            "{}",
+            message
+          );
+        }
+
+        // appending continues the invalid sequences the same way
+        for split_index in 0..bytes.len() {
+          let mut appended = TextLines::from_bytes(&bytes[..split_index], options);
+          appended.append("\u{0301}\n");
+          let mut expected_bytes = bytes[..split_index].to_vec();
+          expected_bytes.extend_from_slice("\u{0301}\n".as_bytes());
+          assert_eq!(
+            appended,
+            TextLines::from_bytes(&expected_bytes, options),
+            "Bytes: {:?}, Split: {}",
+            bytes,
+            split_index
+          );
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn text_lines_conversions() {
+    let lines = TextLines::new("a\tβ\n中c\r\nd");
+    let range = lines.line_and_column_range(2..8);
+    assert_eq!(
+      range,
+      LineAndColumnRange {
+        start: LineAndColumnIndex {
+          line_index: 0,
+          column_index: 2,
+        },
+        end: LineAndColumnIndex {
+          line_index: 1,
+          column_index: 1,
+        },
+      }
+    );
+    assert_eq!(lines.byte_range(range), (2..8).into());
+    let utf16_range = lines.line_and_column_range_with_encoding(5..9, ColumnEncoding::Utf16);
+    assert_eq!(utf16_range.end.column_index, 2);
+    assert_eq!(
+      lines.byte_range_with_encoding(utf16_range, ColumnEncoding::Utf16),
+      (5..9).into()
+    );
+
+    let display_range = lines.display_range(0..9);
+    assert_eq!(
+      display_range,
+      DisplayRange {
+        start: LineAndColumnDisplay {
+          line_number: 1,
+          column_number: 1,
+        },
+        end: LineAndColumnDisplay {
+          line_number: 2,
+          column_number: 4,
+        },
+      }
+    );
+    assert_eq!(lines.byte_range_from_display(display_range), (0..9).into());
+    // columns inside a wide character are expanded to contain it
+    let inside_range = DisplayRange {
+      start: LineAndColumnDisplay {
+        line_number: 2,
+        column_number: 2,
+      },
+      end: LineAndColumnDisplay {
+        line_number: 2,
+        column_number: 2,
+      },
+    };
+    assert_eq!(lines.byte_range_from_display(inside_range), (5..8).into());
+
+    assert_eq!(
+      lines.try_line_and_column_range(ByteRange { start: 3, end: 2 }),
+      Err(TextLinesError::InvalidRange { start: 3, end: 2 })
+    );
+    assert_eq!(
+      lines.try_display_range(0..20),
+      Err(TextLinesError::ByteIndexOutOfRange {
+        byte_index: 20,
+        text_length: 12,
+      })
+    );
+  }
+
+  #[test]
+  fn line_segments() {
+    let lines = TextLines::new("ab\tc\r\n中d\n\nef");
+    let segments = lines.line_segments(1..14);
+    assert_eq!(
+      segments,
+      vec![
+        LineSegment {
+          line_index: 0,
+          byte_range: (1..4).into(),
+          columns: 1..4,
+          display_columns: 1..7,
+          starts_here: true,
+          ends_here: false,
+          continues: false,
+        },
+        LineSegment {
+          line_index: 1,
+          byte_range: (6..10).into(),
+          columns: 0..2,
+          display_columns: 0..3,
+          starts_here: false,
+          ends_here: false,
+          continues: true,
+        },
+        LineSegment {
+          line_index: 2,
+          byte_range: (11..11).into(),
+          columns: 0..0,
+          display_columns: 0..0,
+          starts_here: false,
+          ends_here: false,
+          continues: true,
+        },
+        LineSegment {
+          line_index: 3,
+          byte_range: (12..14).into(),
+          columns: 0..2,
+          display_columns: 0..2,
+          starts_here: false,
+          ends_here: true,
+          continues: false,
+        },
+      ]
+    );
+
+    // ending at the start of a line ends on the previous line
+    let segments = lines.line_segments(1..11);
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[1].byte_range, (6..10).into());
+    assert!(segments[1].ends_here);
+
+    // an empty range has a single segment
+    let segments = lines.line_segments(6..6);
+    assert_eq!(segments.len(), 1);
+    assert!(segments[0].starts_here && segments[0].ends_here);
+    assert_eq!(segments[0].display_columns, 0..0);
+
+    assert_eq!(
+      lines.try_line_segments(0..15),
+      Err(TextLinesError::ByteIndexOutOfRange {
+        byte_index: 15,
+        text_length: 14,
+      })
+    );
+  }
+
+  #[test]
+  fn truncated_line() {
+    let text = "0123456789abcdefghij\nab\tcdefgh";
+    let info = TextLines::new(text);
+    let assert_truncated = |byte_index, max_width, expected_text: &str, display_index| {
+      let truncated = info.truncated_line(text, byte_index, max_width);
+      assert_eq!(truncated.text, expected_text);
+      assert_eq!(truncated.display_index, display_index);
+    };
+    assert_truncated(10, 10, "…6789abcd…", 5);
+    assert_truncated(0, 10, "012345678…", 0);
+    assert_truncated(20, 10, "…bcdefghij", 10);
+    assert_truncated(25, 20, "ab    cdefgh", 7);
+    // the tab is kept whole
+    assert_truncated(23, 6, "…    …", 1);
+    assert_truncated(24, 7, "…cde…", 1);
+    assert_truncated(10, 3, "…a…", 1);
+    assert_truncated(20, 2, "…j", 2);
+    assert_eq!(info.truncated_line(text, 10, 10).byte_range, (6..14).into());
+    assert_eq!(
+      info.try_truncated_line(text, 23, 5),
+      Err(TextLinesError::MaxWidthTooSmall {
+        max_width: 5,
+        min_width: 6,
+      })
+    );
+    assert_eq!(
+      info.try_truncated_line(text, 0, 1),
+      Err(TextLinesError::MaxWidthTooSmall {
+        max_width: 1,
+        min_width: 3,
+      })
+    );
+    assert_eq!(
+      info.try_truncated_line("", 0, 10),
+      Err(TextLinesError::TextLengthMismatch {
+        expected: text.len(),
+        actual: 0,
+      })
+    );
+  }
+
+  #[test]
+  fn truncated_line_never_splits_chars() {
+    let text = "a\t中e\u{0301}\t😀b中中\tcd\u{0301}e中";
+    for tab_display in [TabDisplay::FixedWidth, TabDisplay::TabStops] {
+      let info = TextLines::with_options(
+        text,
+        TextLinesOptions {
+          tab_display,
+          ..Default::default()
+        },
+      );
+      let char_boundaries = text
+        .char_indices()
+        .map(|(index, _)| index)
+        .chain(std::iter::once(text.len()));
+      for byte_index in char_boundaries {
+        for max_width in 0..=16 {
+          let message = format!("Byte index: {}, Max width: {}", byte_index, max_width);
+          let truncated = match info.try_truncated_line(text, byte_index, max_width) {
+            Ok(truncated) => truncated,
+            Err(TextLinesError::MaxWidthTooSmall { min_width, .. }) => {
+              assert!(max_width < min_width, "{}", message);
+              assert!(min_width <= 6, "{}", message);
+              continue;
+            }
+            Err(err) => panic!("{} {}", err, message),
+          };
+          let range = truncated.byte_range;
+          assert!(text.is_char_boundary(range.start), "{}", message);
+          assert!(text.is_char_boundary(range.end), "{}", message);
+          assert!(range.start <= byte_index, "{}", message);
+          assert!(
+            byte_index < range.end || byte_index == text.len(),
+            "{}",
+            message
+          );
+          let width = |text: &str| text.chars().map(|c| c.width().unwrap_or(0)).sum::<usize>();
+          assert!(width(&truncated.text) <= max_width, "{}", message);
+          let marker_width = if range.start > 0 { 1 } else { 0 };
+          assert_eq!(
+            truncated.display_index,
+            marker_width + width(&info.expand_tabs(text, range.start..byte_index)),
+            "{}",
+            message
+          );
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn char_index_matches_linear_scan() {
+    for_each_oracle(|text, _, info| {
+      let byte_indexes = text
+        .char_indices()
+        .map(|(index, _)| index)
+        .chain(text.len()..text.len() + 2);
+      for byte_index in byte_indexes {
+        assert_eq!(
+          info.char_index(byte_index),
+          char_index_linear(info, byte_index),
+          "Text: {:?}, Byte index: {}",
+          text,
+          byte_index
+        );
+      }
+      for char_index in 0..text.len() + 2 {
+        assert_eq!(
+          info.byte_index_from_char_index(char_index),
+          byte_index_from_char_index_linear(info, char_index),
+          "Text: {:?}, Char index: {}",
+          text,
+          char_index
+        );
+      }
+    });
+  }
+
+  #[test]
+  fn columns_match_naive_scan_on_long_lines() {
+    let segment = "a\tβ😀中\u{0301}bc\t\t🇺🇸e\u{200B}d";
+    let text = format!(
+      "{}\n\t{}\r\n{}",
+      segment.repeat(50),
+      segment.repeat(20),
+      segment
+    );
+    for tab_display in [TabDisplay::FixedWidth, TabDisplay::TabStops] {
+      let info = TextLines::with_options(
+        &text,
+        TextLinesOptions {
+          tab_display,
+          ..Default::default()
+        },
+      );
+      for line_index in 0..info.lines_count() {
+        let (line_start, line_end) = info.line_range(line_index);
+        let line_text = &text[line_start..line_end];
+        let grapheme_starts = line_text
+          .grapheme_indices(true)
+          .map(|(index, _)| index)
+          .chain(std::iter::once(line_text.len()))
+          .collect::<Vec<_>>();
+        let byte_indexes = line_text
+          .char_indices()
+          .map(|(index, _)| index)
+          .chain(std::iter::once(line_text.len()));
+        for column_byte_index in byte_indexes {
+          let byte_index = line_start + column_byte_index;
+          let before_text = &line_text[..column_byte_index];
+          let expected_columns = [
+            (ColumnEncoding::Utf8, column_byte_index),
+            (ColumnEncoding::Utf16, before_text.encode_utf16().count()),
+            (ColumnEncoding::Utf32, before_text.chars().count()),
+            (
+              ColumnEncoding::Graphemes,
+              grapheme_starts.partition_point(|index| *index <= column_byte_index) - 1,
+            ),
+          ];
+          for (encoding, column_index) in expected_columns {
+            let message = format!("Byte index: {}, Encoding: {:?}", byte_index, encoding);
+            assert_eq!(
+              info.line_and_column_index_with_encoding(byte_index, encoding),
+              LineAndColumnIndex {
+                line_index,
+                column_index
+              },
+              "{}",
+              message
+            );
+            if encoding != ColumnEncoding::Graphemes || grapheme_starts.contains(&column_byte_index)
+            {
+              let line_and_column = LineAndColumnIndex {
+                line_index,
+                column_index,
+              };
+              assert_eq!(
+                info.byte_index_with_encoding(line_and_column, encoding),
+                byte_index,
+                "{}",
+                message
+              );
+            }
+          }
+          for indent_width in [4, 3] {
+            assert_eq!(
+              info.line_and_column_display_with_indent_width(byte_index, indent_width),
+              LineAndColumnDisplay {
+                line_number: line_index + 1,
+                column_number: display_index_naive(before_text, tab_display, indent_width) + 1,
+              },
+              "Byte index: {}, Indent width: {}",
+              byte_index,
+              indent_width
+            );
+          }
+        }
+      }
+    }
+  }
+
+  fn display_index_naive(text: &str, tab_display: TabDisplay, indent_width: usize) -> usize {
+    let mut display_index = 0;
+    for c in text.chars() {
+      display_index += match c {
+        '\t' if tab_display == TabDisplay::TabStops => indent_width - display_index % indent_width,
+        '\t' => indent_width,
+        _ if c.is_ascii() => 1,
+        _ => c.width().unwrap_or(0),
+      };
+    }
+    display_index
+  }
+
+  #[test]
+  fn memory_usage() {
+    let usize_size = std::mem::size_of::<usize>();
+    let text = "function test() {\n  return 1 + 2;\n}\n".repeat(1000);
+    let info = TextLines::new(&text);
+    let heap_size = info.memory_usage() - std::mem::size_of::<TextLines>();
+    // nothing is stored for the characters of ASCII text, so the line
+    // information takes less than the line starts, ends and character
+    // starts would as usizes
+    assert!(heap_size < info.lines_count() * 3 * usize_size);
+
+    let text = format!("{}\n", "β\t".repeat(1000));
+    let info = TextLines::new(&text);
+    let heap_size = info.memory_usage() - std::mem::size_of::<TextLines>();
+    // the information about the characters is packed as well
+    let line_columns = 6;
+    let multi_byte_char_columns = 6;
+    let tab_char_columns = 2;
+    assert!(
+      heap_size
+        < (info.lines_count() * line_columns + 1000 * (multi_byte_char_columns + tab_char_columns))
+          * usize_size
+    );
+  }
+
+  /// The previous implementation, which scanned every line.
+  fn char_index_linear(info: &TextLines, byte_index: usize) -> usize {
+    let mut lines = (0..info.lines.len())
+      .map(|line_index| info.lines.line(line_index))
+      .peekable();
+    let mut last_char_index = 0;
+    let mut last_byte_index = 0;
+    while let Some(line) = lines.next() {
+      for char_info in line.multi_byte_chars() {
+        if char_info.byte_index >= byte_index {
+          return last_char_index + byte_index - last_byte_index;
+        } else {
+          let char_length = char_info.byte_index - last_byte_index;
+          last_byte_index = char_info.byte_index + char_info.length;
+          last_char_index += char_length + 1;
+        }
+      }
+      let line_end = match lines.peek() {
+        Some(next_line) => next_line.start_index,
+        None => line.end_index,
+      };
+      if line_end >= byte_index {
+        return last_char_index + byte_index - last_byte_index;
+      } else {
+        last_char_index += line_end - last_byte_index;
+        last_byte_index = line_end;
+      }
+    }
+    last_char_index
+  }
+
+  /// The previous implementation, which scanned every line.
+  fn byte_index_from_char_index_linear(info: &TextLines, char_index: usize) -> usize {
+    let mut last_char_index = 0;
+    let mut last_byte_index = 0;
+    let mut lines = (0..info.lines.len())
+      .map(|line_index| info.lines.line(line_index))
+      .peekable();
+    while let Some(line) = lines.next() {
+      for char_info in line.multi_byte_chars() {
+        let char_length = char_info.byte_index - last_byte_index;
+        if last_char_index + char_length >= char_index {
+          return last_byte_index + char_index - last_char_index;
+        } else {
+          last_byte_index = char_info.byte_index + char_info.length;
+          last_char_index += char_length + 1;
+        }
+      }
+      let line_end = match lines.peek() {
+        Some(next_line) => next_line.start_index,
+        None => line.end_index,
+      };
+      let char_length = line_end - last_byte_index;
+      if last_char_index + char_length >= char_index {
+        return last_byte_index + char_index - last_char_index;
+      } else {
+        last_byte_index = line_end;
+        last_char_index += char_length;
+      }
+    }
+    last_byte_index
+  }
+
   #[test]
   fn readme_example() {
     let text = "Line 1\n\tLine 2";
@@ -0,0 +1,137 @@
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::ops::Range;
+
+use crate::TextLines;
+use crate::TextLinesError;
+use crate::TextLinesOptions;
+
+/// Reads the text of lines from a seekable reader, such as a file, using
+/// the information about its lines.
+///
+/// Only the bytes of the requested lines are read, so the text does not
+/// need to be held in memory.
+#[derive(Debug)]
+pub struct LineReader<R: Read + Seek> {
+  reader: R,
+  lines: TextLines,
+}
+
+impl<R: Read + Seek> LineReader<R> {
+  /// Creates a new `LineReader` from a reader and the information about the
+  /// lines of its text.
+  pub fn new(reader: R, lines: TextLines) -> Self {
+    LineReader { reader, lines }
+  }
+
+  /// Creates a new `LineReader` by reading the entire text from the start
+  /// of the reader to get the information about its lines.
+  pub fn with_options(mut reader: R, options: TextLinesOptions) -> io::Result<Self> {
+    reader.seek(SeekFrom::Start(0))?;
+    let lines = TextLines::from_reader(&mut reader, options)?;
+    Ok(LineReader { reader, lines })
+  }
+
+  /// Gets the information about the lines of the text.
+  pub fn text_lines(&self) -> &TextLines {
+    &self.lines
+  }
+
+  /// Takes the reader.
+  pub fn into_inner(self) -> R {
+    self.reader
+  }
+
+  /// Reads the text of the line without the newline.
+  pub fn read_line(&mut self, line_index: usize) -> io::Result<String> {
+    let (start, end) = self
+      .lines
+      .try_line_range(line_index)
+      .map_err(invalid_input)?;
+    self.read(start..end)
+  }
+
+  /// Reads the text of the line including its newline, if any.
+  pub fn read_line_with_terminator(&mut self, line_index: usize) -> io::Result<String> {
+    let start = self
+      .lines
+      .try_line_start(line_index)
+      .map_err(invalid_input)?;
+    let end = if line_index + 1 < self.lines.lines_count() {
+      self.lines.line_start(line_index + 1)
+    } else {
+      self.lines.text_length()
+    };
+    self.read(start..end)
+  }
+
+  /// Reads the text in the provided byte range.
+  pub fn read_range(&mut self, range: Range<usize>) -> io::Result<String> {
+    if range.start > range.end {
+      return Err(invalid_input(TextLinesError::InvalidRange {
+        start: range.start,
+        end: range.end,
+      }));
+    }
+    if range.end > self.lines.text_length() {
+      return Err(invalid_input(TextLinesError::ByteIndexOutOfRange {
+        byte_index: range.end,
+        text_length: self.lines.text_length(),
+      }));
+    }
+    self.read(range)
+  }
+
+  fn read(&mut self, range: Range<usize>) -> io::Result<String> {
+    let mut bytes = vec![0; range.end - range.start];
+    self.reader.seek(SeekFrom::Start(range.start as u64))?;
+    self.reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+  }
+}
+
+fn invalid_input(err: TextLinesError) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidInput, err)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use super::*;
+
+  #[test]
+  fn read_line() {
+    let text = "\u{FEFF}1\r\nβ2\n\n3";
+    let mut reader = LineReader::with_options(Cursor::new(text), Default::default()).unwrap();
+    assert_eq!(reader.text_lines().lines_count(), 4);
+    assert_eq!(reader.read_line(1).unwrap(), "β2");
+    assert_eq!(reader.read_line(0).unwrap(), "1");
+    assert_eq!(reader.read_line(2).unwrap(), "");
+    assert_eq!(reader.read_line(3).unwrap(), "3");
+    assert_eq!(reader.read_line_with_terminator(0).unwrap(), "1\r\n");
+    assert_eq!(reader.read_line_with_terminator(3).unwrap(), "3");
+    assert_eq!(reader.read_range(5..9).unwrap(), "\nβ2");
+
+    let err = reader.read_line(4).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(
+      err.to_string(),
+      "The specified line index 4 was greater or equal to the number of lines of 4."
+    );
+    let err = reader.read_range(7..8).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+  }
+
+  #[test]
+  fn read_line_changed_reader() {
+    let lines = TextLines::new("1\n2\n3");
+    let mut reader = LineReader::new(Cursor::new("1\n2"), lines);
+    assert_eq!(reader.read_line(1).unwrap(), "2");
+    let err = reader.read_line(2).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    assert_eq!(reader.into_inner().into_inner(), "1\n2");
+  }
+}
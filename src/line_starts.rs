@@ -0,0 +1,363 @@
+use crate::ascii_scan;
+use crate::line_table::line_end_index;
+use crate::line_table::scan_lines;
+use crate::line_table::LinePosition;
+use crate::line_table::LineTable;
+use crate::line_table::PackedVec;
+use crate::unwrap_or_panic;
+use crate::ColumnEncoding;
+use crate::ColumnMeasure;
+use crate::LineAndColumnDisplay;
+use crate::LineAndColumnIndex;
+use crate::TextLinesError;
+use crate::TextLinesOptions;
+use crate::BOM_CHAR;
+
+/// The boundaries of the lines of a text.
+///
+/// This is a lighter weight alternative to `TextLines` for when mostly only
+/// line information is necessary. Only the line start and end indexes are
+/// stored, so column information is computed from the provided text when
+/// requested, which takes time proportional to the length of the line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineStarts {
+  line_starts: PackedVec,
+  line_ends: PackedVec,
+  options: TextLinesOptions,
+}
+
+impl LineStarts {
+  /// Creates a new `LineStarts` with the specified text.
+  pub fn new(text: &str) -> Self {
+    LineStarts::with_options(text, Default::default())
+  }
+
+  /// Creates a new `LineStarts` with the specified text and options.
+  ///
+  /// The indent width and tab display are only used when getting the
+  /// display columns.
+  pub fn with_options(text: &str, options: TextLinesOptions) -> Self {
+    let line_terminators = options.line_terminators;
+    let include_vt_ff = line_terminators.is_other_terminator('\u{0B}');
+    let bytes = text.as_bytes();
+    let mut line_starts = PackedVec::default();
+    let mut line_ends = PackedVec::default();
+    let mut last_line_start = if text.starts_with(BOM_CHAR) {
+      BOM_CHAR.len_utf8()
+    } else {
+      0
+    };
+    let mut byte_index = last_line_start;
+    loop {
+      byte_index = ascii_scan::find_special_byte(bytes, byte_index, include_vt_ff);
+      let c = match text[byte_index..].chars().next() {
+        Some(c) => c,
+        None => break,
+      };
+      let line_end_index = line_end_index(c, bytes, byte_index, line_terminators);
+      byte_index += c.len_utf8();
+      if let Some(end_index) = line_end_index {
+        line_starts.push(last_line_start);
+        line_ends.push(end_index);
+        last_line_start = byte_index;
+      }
+    }
+    line_starts.push(last_line_start);
+    line_ends.push(text.len());
+    line_starts.shrink_to_fit();
+    line_ends.shrink_to_fit();
+
+    LineStarts {
+      line_starts,
+      line_ends,
+      options,
+    }
+  }
+
+  /// Gets the number of lines in the text.
+  pub fn lines_count(&self) -> usize {
+    self.line_starts.len()
+  }
+
+  /// Gets the text length in bytes.
+  pub fn text_length(&self) -> usize {
+    self.line_ends.last().unwrap()
+  }
+
+  /// Gets the line index from a byte index.
+  /// Note that if you provide the middle byte index of a \r\n newline
+  /// then it will return the index of the preceding line.
+  pub fn line_index(&self, byte_index: usize) -> usize {
+    unwrap_or_panic(self.try_line_index(byte_index))
+  }
+
+  /// Fallible version of [`line_index`](Self::line_index).
+  pub fn try_line_index(&self, byte_index: usize) -> Result<usize, TextLinesError> {
+    if byte_index > self.text_length() {
+      return Err(TextLinesError::ByteIndexOutOfRange {
+        byte_index,
+        text_length: self.text_length(),
+      });
+    }
+    Ok(self.line_starts.line_index_of(byte_index))
+  }
+
+  /// Gets the line start byte index.
+  pub fn line_start(&self, line_index: usize) -> usize {
+    unwrap_or_panic(self.try_line_start(line_index))
+  }
+
+  /// Fallible version of [`line_start`](Self::line_start).
+  pub fn try_line_start(&self, line_index: usize) -> Result<usize, TextLinesError> {
+    self.validate_line_index(line_index)?;
+    Ok(self.line_starts.get(line_index))
+  }
+
+  /// Gets the line end byte index (before the newline character).
+  pub fn line_end(&self, line_index: usize) -> usize {
+    unwrap_or_panic(self.try_line_end(line_index))
+  }
+
+  /// Fallible version of [`line_end`](Self::line_end).
+  pub fn try_line_end(&self, line_index: usize) -> Result<usize, TextLinesError> {
+    self.validate_line_index(line_index)?;
+    Ok(self.line_ends.get(line_index))
+  }
+
+  /// Gets the line range.
+  pub fn line_range(&self, line_index: usize) -> (usize, usize) {
+    unwrap_or_panic(self.try_line_range(line_index))
+  }
+
+  /// Fallible version of [`line_range`](Self::line_range).
+  pub fn try_line_range(&self, line_index: usize) -> Result<(usize, usize), TextLinesError> {
+    self.validate_line_index(line_index)?;
+    Ok((
+      self.line_starts.get(line_index),
+      self.line_ends.get(line_index),
+    ))
+  }
+
+  /// Gets the byte position from the provided line and column index
+  /// in the text the lines were created with.
+  pub fn byte_index(&self, text: &str, line_and_column: LineAndColumnIndex) -> usize {
+    self.byte_index_with_encoding(text, line_and_column, ColumnEncoding::Utf32)
+  }
+
+  /// Fallible version of [`byte_index`](Self::byte_index).
+  pub fn try_byte_index(
+    &self,
+    text: &str,
+    line_and_column: LineAndColumnIndex,
+  ) -> Result<usize, TextLinesError> {
+    self.try_byte_index_with_encoding(text, line_and_column, ColumnEncoding::Utf32)
+  }
+
+  /// Gets the byte position from the provided line and column index in the
+  /// text the lines were created with where the column is measured in the
+  /// provided encoding.
+  pub fn byte_index_with_encoding(
+    &self,
+    text: &str,
+    line_and_column: LineAndColumnIndex,
+    encoding: ColumnEncoding,
+  ) -> usize {
+    unwrap_or_panic(self.try_byte_index_with_encoding(text, line_and_column, encoding))
+  }
+
+  /// Fallible version of [`byte_index_with_encoding`](Self::byte_index_with_encoding).
+  pub fn try_byte_index_with_encoding(
+    &self,
+    text: &str,
+    line_and_column: LineAndColumnIndex,
+    encoding: ColumnEncoding,
+  ) -> Result<usize, TextLinesError> {
+    let line_table = self.scan_line(text, line_and_column.line_index)?;
+    Ok(
+      line_table
+        .line(0)
+        .byte_index(line_and_column.column_index, encoding),
+    )
+  }
+
+  /// Gets the line and column index of the provided byte index in the
+  /// text the lines were created with.
+  pub fn line_and_column_index(&self, text: &str, byte_index: usize) -> LineAndColumnIndex {
+    self.line_and_column_index_with_encoding(text, byte_index, ColumnEncoding::Utf32)
+  }
+
+  /// Fallible version of [`line_and_column_index`](Self::line_and_column_index).
+  pub fn try_line_and_column_index(
+    &self,
+    text: &str,
+    byte_index: usize,
+  ) -> Result<LineAndColumnIndex, TextLinesError> {
+    self.try_line_and_column_index_with_encoding(text, byte_index, ColumnEncoding::Utf32)
+  }
+
+  /// Gets the line and column index of the provided byte index in the text
+  /// the lines were created with where the column is measured in the
+  /// provided encoding.
+  pub fn line_and_column_index_with_encoding(
+    &self,
+    text: &str,
+    byte_index: usize,
+    encoding: ColumnEncoding,
+  ) -> LineAndColumnIndex {
+    unwrap_or_panic(self.try_line_and_column_index_with_encoding(text, byte_index, encoding))
+  }
+
+  /// Fallible version of [`line_and_column_index_with_encoding`](Self::line_and_column_index_with_encoding).
+  pub fn try_line_and_column_index_with_encoding(
+    &self,
+    text: &str,
+    byte_index: usize,
+    encoding: ColumnEncoding,
+  ) -> Result<LineAndColumnIndex, TextLinesError> {
+    let line_index = self.try_line_index(byte_index)?;
+    let line_table = self.scan_line(text, line_index)?;
+    Ok(LineAndColumnIndex {
+      line_index,
+      column_index: line_table
+        .line(0)
+        .column_index(byte_index, ColumnMeasure::Encoding(encoding)),
+    })
+  }
+
+  /// Gets the line and column display based on the indentation width and
+  /// the provided byte index in the text the lines were created with.
+  pub fn line_and_column_display(&self, text: &str, byte_index: usize) -> LineAndColumnDisplay {
+    unwrap_or_panic(self.try_line_and_column_display(text, byte_index))
+  }
+
+  /// Fallible version of [`line_and_column_display`](Self::line_and_column_display).
+  pub fn try_line_and_column_display(
+    &self,
+    text: &str,
+    byte_index: usize,
+  ) -> Result<LineAndColumnDisplay, TextLinesError> {
+    let line_index = self.try_line_index(byte_index)?;
+    let line_table = self.scan_line(text, line_index)?;
+    let column_index = line_table.line(0).display_index(
+      byte_index,
+      ColumnMeasure::DisplayWidth,
+      self.options.indent_width,
+      &self.options,
+    );
+    Ok(LineAndColumnDisplay {
+      line_number: line_index + 1,
+      column_number: column_index + 1,
+    })
+  }
+
+  /// Gets the information about the characters of the line.
+  fn scan_line(&self, text: &str, line_index: usize) -> Result<LineTable, TextLinesError> {
+    if text.len() != self.text_length() {
+      return Err(TextLinesError::TextLengthMismatch {
+        expected: self.text_length(),
+        actual: text.len(),
+      });
+    }
+    let line_start = self.try_line_start(line_index)?;
+    // the character index isn't used, so start it at zero
+    let (line_table, _) = scan_lines(
+      &text.as_bytes()[line_start..],
+      LinePosition::line_start(line_start, 0),
+      &self.options,
+      |_| true,
+    );
+    Ok(line_table)
+  }
+
+  fn validate_line_index(&self, line_index: usize) -> Result<(), TextLinesError> {
+    if line_index >= self.lines_count() {
+      Err(TextLinesError::LineIndexOutOfRange {
+        line_index,
+        lines_count: self.lines_count(),
+      })
+    } else {
+      Ok(())
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_helpers::for_each_oracle;
+
+  #[test]
+  fn matches_text_lines() {
+    for_each_oracle(|text, options, lines| {
+      let line_starts = LineStarts::with_options(text, options);
+      assert_eq!(line_starts.lines_count(), lines.lines_count());
+      assert_eq!(line_starts.text_length(), lines.text_length());
+      for line_index in 0..lines.lines_count() {
+        assert_eq!(
+          line_starts.line_range(line_index),
+          lines.line_range(line_index),
+          "Text: {:?}, Line index: {}",
+          text,
+          line_index
+        );
+        for encoding in [ColumnEncoding::Utf16, ColumnEncoding::Graphemes] {
+          let line_and_column = LineAndColumnIndex {
+            line_index,
+            column_index: 1,
+          };
+          assert_eq!(
+            line_starts.byte_index_with_encoding(text, line_and_column, encoding),
+            lines.byte_index_with_encoding(line_and_column, encoding),
+          );
+        }
+      }
+      for byte_index in 0..=text.len() {
+        let message = format!("Text: {:?}, Byte index: {}", text, byte_index);
+        assert_eq!(
+          line_starts.line_index(byte_index),
+          lines.line_index(byte_index),
+          "{}",
+          message
+        );
+        assert_eq!(
+          line_starts.line_and_column_index_with_encoding(text, byte_index, ColumnEncoding::Utf16),
+          lines.line_and_column_index_with_encoding(byte_index, ColumnEncoding::Utf16),
+          "{}",
+          message
+        );
+        assert_eq!(
+          line_starts.line_and_column_display(text, byte_index),
+          lines.line_and_column_display(byte_index),
+          "{}",
+          message
+        );
+      }
+    });
+  }
+
+  #[test]
+  fn errors() {
+    let line_starts = LineStarts::new("1\n2");
+    assert_eq!(
+      line_starts.try_line_index(4),
+      Err(TextLinesError::ByteIndexOutOfRange {
+        byte_index: 4,
+        text_length: 3,
+      })
+    );
+    assert_eq!(
+      line_starts.try_line_range(2),
+      Err(TextLinesError::LineIndexOutOfRange {
+        line_index: 2,
+        lines_count: 2,
+      })
+    );
+    assert_eq!(
+      line_starts.try_line_and_column_index("1\n", 1),
+      Err(TextLinesError::TextLengthMismatch {
+        expected: 3,
+        actual: 2,
+      })
+    );
+  }
+}
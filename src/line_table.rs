@@ -0,0 +1,1243 @@
+use std::ops::Range;
+use std::ops::RangeFrom;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+use crate::ascii_scan;
+use crate::ColumnEncoding;
+use crate::ColumnMeasure;
+use crate::LineTerminators;
+use crate::TabDisplay;
+use crate::TextLinesOptions;
+use crate::BOM_CHAR;
+
+const BOM_BYTES: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+/// A list of unsigned integers stored using the smallest width that fits
+/// all of them, which is widened as larger values are added.
+#[derive(Debug, Clone)]
+pub(crate) enum PackedVec {
+  U8(Vec<u8>),
+  U32(Vec<u32>),
+  Usize(Vec<usize>),
+}
+
+impl Default for PackedVec {
+  fn default() -> Self {
+    PackedVec::U8(Vec::new())
+  }
+}
+
+impl PartialEq for PackedVec {
+  fn eq(&self, other: &Self) -> bool {
+    // the values may have been widened differently
+    self.len() == other.len() && self.iter().eq(other.iter())
+  }
+}
+
+impl Eq for PackedVec {}
+
+impl PackedVec {
+  pub fn len(&self) -> usize {
+    match self {
+      PackedVec::U8(values) => values.len(),
+      PackedVec::U32(values) => values.len(),
+      PackedVec::Usize(values) => values.len(),
+    }
+  }
+
+  pub fn get(&self, index: usize) -> usize {
+    match self {
+      PackedVec::U8(values) => values[index] as usize,
+      PackedVec::U32(values) => values[index] as usize,
+      PackedVec::Usize(values) => values[index],
+    }
+  }
+
+  pub fn last(&self) -> Option<usize> {
+    self.len().checked_sub(1).map(|index| self.get(index))
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = usize> + Clone + '_ {
+    (0..self.len()).map(move |index| self.get(index))
+  }
+
+  pub fn push(&mut self, value: usize) {
+    self.widen_for(value);
+    match self {
+      PackedVec::U8(values) => values.push(value as u8),
+      PackedVec::U32(values) => values.push(value as u32),
+      PackedVec::Usize(values) => values.push(value),
+    }
+  }
+
+  /// Updates each value from the start of the range with the result of `f`.
+  pub fn update(&mut self, range: RangeFrom<usize>, f: impl Fn(usize) -> usize) {
+    for index in range.start..self.len() {
+      let value = f(self.get(index));
+      self.widen_for(value);
+      match self {
+        PackedVec::U8(values) => values[index] = value as u8,
+        PackedVec::U32(values) => values[index] = value as u32,
+        PackedVec::Usize(values) => values[index] = value,
+      }
+    }
+  }
+
+  /// Replaces the values in the range with the provided values.
+  pub fn splice(&mut self, range: Range<usize>, values: impl Iterator<Item = usize> + Clone) {
+    if let Some(max_value) = values.clone().max() {
+      self.widen_for(max_value);
+    }
+    match self {
+      PackedVec::U8(current) => {
+        current.splice(range, values.map(|value| value as u8));
+      }
+      PackedVec::U32(current) => {
+        current.splice(range, values.map(|value| value as u32));
+      }
+      PackedVec::Usize(current) => {
+        current.splice(range, values);
+      }
+    }
+  }
+
+  /// Gets the index of the line that contains the index when the values
+  /// are the indexes that the lines start at.
+  ///
+  /// This is the first line for an index before the first line start,
+  /// which may happen when there's a BOM.
+  pub fn line_index_of(&self, index: usize) -> usize {
+    self
+      .partition_point(0..self.len(), |line_start| line_start <= index)
+      .saturating_sub(1)
+  }
+
+  /// Gets the index of the first value in the range where the predicate
+  /// is false, which is the end of the range when there is none.
+  pub fn partition_point(&self, range: Range<usize>, pred: impl Fn(usize) -> bool) -> usize {
+    range.start
+      + match self {
+        PackedVec::U8(values) => values[range].partition_point(|value| pred(*value as usize)),
+        PackedVec::U32(values) => values[range].partition_point(|value| pred(*value as usize)),
+        PackedVec::Usize(values) => values[range].partition_point(|value| pred(*value)),
+      }
+  }
+
+  pub fn truncate(&mut self, len: usize) {
+    match self {
+      PackedVec::U8(values) => values.truncate(len),
+      PackedVec::U32(values) => values.truncate(len),
+      PackedVec::Usize(values) => values.truncate(len),
+    }
+  }
+
+  pub fn shrink_to_fit(&mut self) {
+    match self {
+      PackedVec::U8(values) => values.shrink_to_fit(),
+      PackedVec::U32(values) => values.shrink_to_fit(),
+      PackedVec::Usize(values) => values.shrink_to_fit(),
+    }
+  }
+
+  /// Gets the number of bytes allocated for the values.
+  pub fn heap_size(&self) -> usize {
+    match self {
+      PackedVec::U8(values) => values.capacity(),
+      PackedVec::U32(values) => values.capacity() * std::mem::size_of::<u32>(),
+      PackedVec::Usize(values) => values.capacity() * std::mem::size_of::<usize>(),
+    }
+  }
+
+  fn widen_for(&mut self, value: usize) {
+    let max_value = match self {
+      PackedVec::U8(_) => u8::MAX as usize,
+      PackedVec::U32(_) => u32::MAX as usize,
+      PackedVec::Usize(_) => usize::MAX,
+    };
+    if value > max_value {
+      let widened = if value <= u32::MAX as usize {
+        PackedVec::U32(self.iter().map(|value| value as u32).collect())
+      } else {
+        PackedVec::Usize(self.iter().collect())
+      };
+      *self = widened;
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MultiByteCharInfo {
+  /// The byte index in the entire file.
+  pub byte_index: usize,
+  /// The character index on the current line.
+  pub line_char_index: usize,
+  /// The UTF-16 code unit index on the current line.
+  pub line_utf16_index: usize,
+  /// The display column index on the current line where tabs take up
+  /// a single column.
+  pub line_display_index: usize,
+  pub length: usize,
+  /// The number of terminal cells the character takes up, which is 2 for
+  /// East Asian wide characters and 0 for zero-width characters.
+  pub display_width: usize,
+}
+
+impl MultiByteCharInfo {
+  /// The number of code units the character takes up in the provided encoding.
+  pub fn encoded_length(&self, encoding: ColumnEncoding) -> usize {
+    match encoding {
+      ColumnEncoding::Utf8 => self.length,
+      // only characters outside the BMP take 4 bytes in UTF-8 and
+      // those are encoded as a surrogate pair in UTF-16
+      ColumnEncoding::Utf16 if self.length == 4 => 2,
+      // grapheme clusters are accounted for at the line level
+      ColumnEncoding::Utf16 | ColumnEncoding::Utf32 | ColumnEncoding::Graphemes => 1,
+    }
+  }
+
+  /// The number of columns the character takes up in the provided measure.
+  fn column_length(&self, measure: ColumnMeasure) -> usize {
+    match measure {
+      ColumnMeasure::Encoding(encoding) => self.encoded_length(encoding),
+      ColumnMeasure::DisplayWidth => self.display_width,
+    }
+  }
+}
+
+/// The columns of information about the multi-byte characters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct MultiByteChars {
+  byte_indexes: PackedVec,
+  line_char_indexes: PackedVec,
+  line_utf16_indexes: PackedVec,
+  line_display_indexes: PackedVec,
+  lengths: PackedVec,
+  display_widths: PackedVec,
+}
+
+impl MultiByteChars {
+  fn get(&self, index: usize) -> MultiByteCharInfo {
+    MultiByteCharInfo {
+      byte_index: self.byte_indexes.get(index),
+      line_char_index: self.line_char_indexes.get(index),
+      line_utf16_index: self.line_utf16_indexes.get(index),
+      line_display_index: self.line_display_indexes.get(index),
+      length: self.lengths.get(index),
+      display_width: self.display_widths.get(index),
+    }
+  }
+
+  fn push(&mut self, char_info: MultiByteCharInfo) {
+    self.byte_indexes.push(char_info.byte_index);
+    self.line_char_indexes.push(char_info.line_char_index);
+    self.line_utf16_indexes.push(char_info.line_utf16_index);
+    self.line_display_indexes.push(char_info.line_display_index);
+    self.lengths.push(char_info.length);
+    self.display_widths.push(char_info.display_width);
+  }
+
+  /// Gets the column of the character column indexes in the provided measure.
+  fn column_indexes(&self, measure: ColumnMeasure) -> &PackedVec {
+    match measure {
+      ColumnMeasure::Encoding(ColumnEncoding::Utf8) => &self.byte_indexes,
+      ColumnMeasure::Encoding(ColumnEncoding::Utf16) => &self.line_utf16_indexes,
+      ColumnMeasure::Encoding(ColumnEncoding::Utf32 | ColumnEncoding::Graphemes) => {
+        &self.line_char_indexes
+      }
+      ColumnMeasure::DisplayWidth => &self.line_display_indexes,
+    }
+  }
+
+  fn columns(&self) -> [&PackedVec; 6] {
+    [
+      &self.byte_indexes,
+      &self.line_char_indexes,
+      &self.line_utf16_indexes,
+      &self.line_display_indexes,
+      &self.lengths,
+      &self.display_widths,
+    ]
+  }
+
+  fn columns_mut(&mut self) -> [&mut PackedVec; 6] {
+    [
+      &mut self.byte_indexes,
+      &mut self.line_char_indexes,
+      &mut self.line_utf16_indexes,
+      &mut self.line_display_indexes,
+      &mut self.lengths,
+      &mut self.display_widths,
+    ]
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TabCharInfo {
+  /// The byte index in the entire file.
+  pub byte_index: usize,
+  /// The display column index on the current line based on the
+  /// indent width and tab display the lines were created with.
+  pub line_display_index: usize,
+}
+
+/// The columns of information about the tab characters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct TabChars {
+  byte_indexes: PackedVec,
+  line_display_indexes: PackedVec,
+}
+
+impl TabChars {
+  fn get(&self, index: usize) -> TabCharInfo {
+    TabCharInfo {
+      byte_index: self.byte_indexes.get(index),
+      line_display_index: self.line_display_indexes.get(index),
+    }
+  }
+
+  fn push(&mut self, tab_char: TabCharInfo) {
+    self.byte_indexes.push(tab_char.byte_index);
+    self.line_display_indexes.push(tab_char.line_display_index);
+  }
+
+  fn columns(&self) -> [&PackedVec; 2] {
+    [&self.byte_indexes, &self.line_display_indexes]
+  }
+
+  fn columns_mut(&mut self) -> [&mut PackedVec; 2] {
+    [&mut self.byte_indexes, &mut self.line_display_indexes]
+  }
+}
+
+/// A grapheme cluster made up of more than one character.
+#[derive(Debug, Clone, Copy)]
+struct GraphemeClusterInfo {
+  /// The byte index in the entire file.
+  byte_index: usize,
+  /// The character index on the current line.
+  line_char_index: usize,
+  /// The grapheme cluster index on the current line.
+  line_grapheme_index: usize,
+  length: usize,
+  char_count: usize,
+}
+
+/// The columns of information about the grapheme clusters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct GraphemeClusters {
+  byte_indexes: PackedVec,
+  line_char_indexes: PackedVec,
+  line_grapheme_indexes: PackedVec,
+  lengths: PackedVec,
+  char_counts: PackedVec,
+}
+
+impl GraphemeClusters {
+  fn get(&self, index: usize) -> GraphemeClusterInfo {
+    GraphemeClusterInfo {
+      byte_index: self.byte_indexes.get(index),
+      line_char_index: self.line_char_indexes.get(index),
+      line_grapheme_index: self.line_grapheme_indexes.get(index),
+      length: self.lengths.get(index),
+      char_count: self.char_counts.get(index),
+    }
+  }
+
+  fn push(&mut self, cluster: GraphemeClusterInfo) {
+    self.byte_indexes.push(cluster.byte_index);
+    self.line_char_indexes.push(cluster.line_char_index);
+    self.line_grapheme_indexes.push(cluster.line_grapheme_index);
+    self.lengths.push(cluster.length);
+    self.char_counts.push(cluster.char_count);
+  }
+
+  fn columns(&self) -> [&PackedVec; 5] {
+    [
+      &self.byte_indexes,
+      &self.line_char_indexes,
+      &self.line_grapheme_indexes,
+      &self.lengths,
+      &self.char_counts,
+    ]
+  }
+
+  fn columns_mut(&mut self) -> [&mut PackedVec; 5] {
+    [
+      &mut self.byte_indexes,
+      &mut self.line_char_indexes,
+      &mut self.line_grapheme_indexes,
+      &mut self.lengths,
+      &mut self.char_counts,
+    ]
+  }
+}
+
+/// Information about the lines of a text.
+///
+/// The information for all the lines is stored in shared flat columns
+/// rather than per line, which avoids allocations per line and allows
+/// storing the values in fewer bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct LineTable {
+  line_starts: PackedVec,
+  line_ends: PackedVec,
+  /// The character index of each line start in the entire file, where
+  /// each byte of a BOM counts as a character.
+  line_char_starts: PackedVec,
+  /// The index after the last multi-byte character of each line.
+  line_multi_byte_char_ends: PackedVec,
+  /// The index after the last tab character of each line.
+  line_tab_char_ends: PackedVec,
+  /// The index after the last grapheme cluster of each line.
+  line_grapheme_cluster_ends: PackedVec,
+  multi_byte_chars: MultiByteChars,
+  tab_chars: TabChars,
+  grapheme_clusters: GraphemeClusters,
+}
+
+impl LineTable {
+  pub fn len(&self) -> usize {
+    self.line_starts.len()
+  }
+
+  pub fn line_start(&self, line_index: usize) -> usize {
+    self.line_starts.get(line_index)
+  }
+
+  pub fn line_end(&self, line_index: usize) -> usize {
+    self.line_ends.get(line_index)
+  }
+
+  pub fn line_char_start(&self, line_index: usize) -> usize {
+    self.line_char_starts.get(line_index)
+  }
+
+  pub fn text_length(&self) -> usize {
+    self.line_ends.last().unwrap()
+  }
+
+  pub fn line(&self, line_index: usize) -> TextLine<'_> {
+    let entry_range = |ends: &PackedVec| {
+      let start = match line_index.checked_sub(1) {
+        Some(previous_line_index) => ends.get(previous_line_index),
+        None => 0,
+      };
+      start..ends.get(line_index)
+    };
+    TextLine {
+      table: self,
+      start_index: self.line_start(line_index),
+      end_index: self.line_end(line_index),
+      char_start_index: self.line_char_start(line_index),
+      multi_byte_chars: entry_range(&self.line_multi_byte_char_ends),
+      tab_chars: entry_range(&self.line_tab_char_ends),
+      grapheme_clusters: entry_range(&self.line_grapheme_cluster_ends),
+    }
+  }
+
+  /// Gets the index of the line that contains the byte index.
+  pub fn line_index(&self, byte_index: usize) -> usize {
+    self.line_starts.line_index_of(byte_index)
+  }
+
+  /// Gets the index of the first line in the range that starts after
+  /// the byte index.
+  pub fn partition_point_line_start(&self, lines: Range<usize>, byte_index: usize) -> usize {
+    self
+      .line_starts
+      .partition_point(lines, |line_start| line_start <= byte_index)
+  }
+
+  /// Gets the index of the first line that starts after the character index.
+  pub fn partition_point_line_char_start(&self, char_index: usize) -> usize {
+    self
+      .line_char_starts
+      .partition_point(0..self.len(), |line_char_start| {
+        line_char_start <= char_index
+      })
+  }
+
+  /// Adds a line that ends after the last added multi-byte and tab characters.
+  ///
+  /// The grapheme clusters of the line are found from the provided position
+  /// in `text`, which starts at `text_index` in the entire text.
+  fn push_line(
+    &mut self,
+    (text, text_index): (&[u8], usize),
+    (start_index, end_index, char_start_index): (usize, usize, usize),
+    grapheme_start: &LinePosition,
+  ) {
+    let multi_byte_char_start = self.line_multi_byte_char_ends.last().unwrap_or(0);
+    let multi_byte_char_end = self.multi_byte_chars.byte_indexes.len();
+    let byte_at = |index: usize| text[index - text_index];
+    // only non-ASCII characters may form a grapheme cluster with another
+    // character other than \r\n, which is never within a line, so only
+    // the text around them is segmented
+    let mut segmented_end = grapheme_start.byte_index;
+    let mut line_char_index = grapheme_start.line_char_index;
+    let mut line_grapheme_index = grapheme_start.line_grapheme_index;
+    for i in multi_byte_char_start..multi_byte_char_end {
+      let char_index = self.multi_byte_chars.byte_indexes.get(i);
+      if char_index >= end_index {
+        break; // a multi-byte line terminator
+      }
+      if char_index < segmented_end {
+        continue;
+      }
+      // the position before an ASCII character that follows another
+      // ASCII character or the segmented text is a cluster boundary
+      let window_start = std::cmp::max(segmented_end, char_index.saturating_sub(1));
+      let mut window_end = char_index + 1;
+      while window_end < end_index
+        && !(byte_at(window_end - 1).is_ascii() && byte_at(window_end).is_ascii())
+      {
+        window_end += 1;
+      }
+      // the skipped text is ASCII, so each byte is a grapheme cluster
+      line_char_index += window_start - segmented_end;
+      line_grapheme_index += window_start - segmented_end;
+
+      let mut byte_index = window_start;
+      let window_text = &text[window_start - text_index..window_end - text_index];
+      for (valid_text, invalid_length) in utf8_chunks(window_text) {
+        for (index, grapheme) in valid_text.grapheme_indices(true) {
+          let char_count = grapheme.chars().count();
+          if char_count > 1 {
+            self.grapheme_clusters.push(GraphemeClusterInfo {
+              byte_index: byte_index + index,
+              line_char_index,
+              line_grapheme_index,
+              length: grapheme.len(),
+              char_count,
+            });
+          }
+          line_char_index += char_count;
+          line_grapheme_index += 1;
+        }
+        byte_index += valid_text.len() + invalid_length;
+        // an invalid sequence is a grapheme cluster on its own
+        if invalid_length > 0 {
+          line_char_index += 1;
+          line_grapheme_index += 1;
+        }
+      }
+      segmented_end = window_end;
+    }
+    self.line_starts.push(start_index);
+    self.line_ends.push(end_index);
+    self.line_char_starts.push(char_start_index);
+    self
+      .line_multi_byte_char_ends
+      .push(self.multi_byte_chars.byte_indexes.len());
+    self
+      .line_tab_char_ends
+      .push(self.tab_chars.byte_indexes.len());
+    self
+      .line_grapheme_cluster_ends
+      .push(self.grapheme_clusters.byte_indexes.len());
+  }
+
+  /// Removes the lines from the provided line index along with the
+  /// information about the characters from the provided byte index.
+  ///
+  /// This leaves the information about the characters before the byte
+  /// index on the line so that the rest of the line may be added back
+  /// with [`extend`](Self::extend).
+  pub fn truncate(&mut self, line_index: usize, byte_index: usize) {
+    for column in [
+      &mut self.line_starts,
+      &mut self.line_ends,
+      &mut self.line_char_starts,
+      &mut self.line_multi_byte_char_ends,
+      &mut self.line_tab_char_ends,
+      &mut self.line_grapheme_cluster_ends,
+    ] {
+      column.truncate(line_index);
+    }
+
+    fn truncate_entries(columns: &mut [&mut PackedVec], byte_index: usize) {
+      // the byte indexes are always the first column
+      let byte_indexes = &columns[0];
+      let len = byte_indexes.partition_point(0..byte_indexes.len(), |index| index < byte_index);
+      for column in columns.iter_mut() {
+        column.truncate(len);
+      }
+    }
+
+    truncate_entries(&mut self.multi_byte_chars.columns_mut(), byte_index);
+    truncate_entries(&mut self.tab_chars.columns_mut(), byte_index);
+    truncate_entries(&mut self.grapheme_clusters.columns_mut(), byte_index);
+  }
+
+  /// Adds the lines of `other` after the lines and the information about the
+  /// characters left by [`truncate`](Self::truncate).
+  pub fn extend(&mut self, other: &LineTable) {
+    let len = self.len();
+    self.line_starts.splice(len..len, other.line_starts.iter());
+    self.line_ends.splice(len..len, other.line_ends.iter());
+    self
+      .line_char_starts
+      .splice(len..len, other.line_char_starts.iter());
+
+    fn extend_entries(
+      line_ends: &mut PackedVec,
+      columns: &mut [&mut PackedVec],
+      other_line_ends: &PackedVec,
+      other_columns: &[&PackedVec],
+    ) {
+      let entry_count = columns[0].len();
+      for (column, other_column) in columns.iter_mut().zip(other_columns.iter()) {
+        let column_len = column.len();
+        column.splice(column_len..column_len, other_column.iter());
+      }
+      let len = line_ends.len();
+      line_ends.splice(
+        len..len,
+        other_line_ends.iter().map(|end| end + entry_count),
+      );
+    }
+
+    extend_entries(
+      &mut self.line_multi_byte_char_ends,
+      &mut self.multi_byte_chars.columns_mut(),
+      &other.line_multi_byte_char_ends,
+      &other.multi_byte_chars.columns(),
+    );
+    extend_entries(
+      &mut self.line_tab_char_ends,
+      &mut self.tab_chars.columns_mut(),
+      &other.line_tab_char_ends,
+      &other.tab_chars.columns(),
+    );
+    extend_entries(
+      &mut self.line_grapheme_cluster_ends,
+      &mut self.grapheme_clusters.columns_mut(),
+      &other.line_grapheme_cluster_ends,
+      &other.grapheme_clusters.columns(),
+    );
+  }
+
+  /// Replaces the lines in the provided range with the lines of `other`
+  /// and moves the lines after them from being relative to `old_index` and
+  /// `old_char_index` to being relative to `new_index` and `new_char_index`.
+  pub fn splice_lines(
+    &mut self,
+    lines: Range<usize>,
+    other: &LineTable,
+    (old_index, new_index): (usize, usize),
+    (old_char_index, new_char_index): (usize, usize),
+  ) {
+    let shift_index = |index: usize| index + new_index - old_index;
+    let shift_char_index = |index: usize| index + new_char_index - old_char_index;
+    self.line_starts.update(lines.end.., shift_index);
+    self.line_ends.update(lines.end.., shift_index);
+    self.line_char_starts.update(lines.end.., shift_char_index);
+    self
+      .line_starts
+      .splice(lines.clone(), other.line_starts.iter());
+    self.line_ends.splice(lines.clone(), other.line_ends.iter());
+    self
+      .line_char_starts
+      .splice(lines.clone(), other.line_char_starts.iter());
+
+    fn splice_entries(
+      line_ends: &mut PackedVec,
+      columns: &mut [&mut PackedVec],
+      lines: &Range<usize>,
+      other_line_ends: &PackedVec,
+      other_columns: &[&PackedVec],
+      shift_index: impl Fn(usize) -> usize,
+    ) {
+      let entry_end = |line_index: usize| match line_index.checked_sub(1) {
+        Some(previous_line_index) => line_ends.get(previous_line_index),
+        None => 0,
+      };
+      let entries = entry_end(lines.start)..entry_end(lines.end);
+      let other_entry_count = other_columns[0].len();
+      // the byte indexes are always the first column
+      columns[0].update(entries.end.., shift_index);
+      for (column, other_column) in columns.iter_mut().zip(other_columns.iter()) {
+        column.splice(entries.clone(), other_column.iter());
+      }
+      line_ends.update(lines.end.., |end| {
+        end + other_entry_count - (entries.end - entries.start)
+      });
+      line_ends.splice(
+        lines.clone(),
+        other_line_ends.iter().map(|end| end + entries.start),
+      );
+    }
+
+    splice_entries(
+      &mut self.line_multi_byte_char_ends,
+      &mut self.multi_byte_chars.columns_mut(),
+      &lines,
+      &other.line_multi_byte_char_ends,
+      &other.multi_byte_chars.columns(),
+      shift_index,
+    );
+    splice_entries(
+      &mut self.line_tab_char_ends,
+      &mut self.tab_chars.columns_mut(),
+      &lines,
+      &other.line_tab_char_ends,
+      &other.tab_chars.columns(),
+      shift_index,
+    );
+    splice_entries(
+      &mut self.line_grapheme_cluster_ends,
+      &mut self.grapheme_clusters.columns_mut(),
+      &lines,
+      &other.line_grapheme_cluster_ends,
+      &other.grapheme_clusters.columns(),
+      shift_index,
+    );
+  }
+
+  pub fn shrink_to_fit(&mut self) {
+    for column in self.columns_mut() {
+      column.shrink_to_fit();
+    }
+  }
+
+  /// Gets the number of bytes allocated for the information.
+  pub fn heap_size(&self) -> usize {
+    let mut columns = vec![
+      &self.line_starts,
+      &self.line_ends,
+      &self.line_char_starts,
+      &self.line_multi_byte_char_ends,
+      &self.line_tab_char_ends,
+      &self.line_grapheme_cluster_ends,
+    ];
+    columns.extend(self.multi_byte_chars.columns());
+    columns.extend(self.tab_chars.columns());
+    columns.extend(self.grapheme_clusters.columns());
+    columns.iter().map(|column| column.heap_size()).sum()
+  }
+
+  fn columns_mut(&mut self) -> Vec<&mut PackedVec> {
+    let mut columns = vec![
+      &mut self.line_starts,
+      &mut self.line_ends,
+      &mut self.line_char_starts,
+      &mut self.line_multi_byte_char_ends,
+      &mut self.line_tab_char_ends,
+      &mut self.line_grapheme_cluster_ends,
+    ];
+    columns.extend(self.multi_byte_chars.columns_mut());
+    columns.extend(self.tab_chars.columns_mut());
+    columns.extend(self.grapheme_clusters.columns_mut());
+    columns
+  }
+}
+
+/// A view of the information about a line.
+pub(crate) struct TextLine<'a> {
+  table: &'a LineTable,
+  pub start_index: usize,
+  pub end_index: usize,
+  /// The character index of the line start in the entire file, where
+  /// each byte of a BOM counts as a character.
+  pub char_start_index: usize,
+  multi_byte_chars: Range<usize>,
+  tab_chars: Range<usize>,
+  grapheme_clusters: Range<usize>,
+}
+
+impl<'a> TextLine<'a> {
+  pub fn multi_byte_chars(&self) -> impl Iterator<Item = MultiByteCharInfo> + 'a {
+    let table = self.table;
+    self
+      .multi_byte_chars
+      .clone()
+      .map(move |index| table.multi_byte_chars.get(index))
+  }
+
+  pub fn tab_chars(&self) -> impl Iterator<Item = TabCharInfo> + 'a {
+    let table = self.table;
+    self
+      .tab_chars
+      .clone()
+      .map(move |index| table.tab_chars.get(index))
+  }
+
+  /// Gets the last multi-byte character where the column index in the
+  /// provided measure is less than the provided column index.
+  fn last_multi_byte_char_before(
+    &self,
+    column_index: usize,
+    measure: ColumnMeasure,
+  ) -> Option<MultiByteCharInfo> {
+    // the utf-8 column indexes are the byte indexes
+    let column_index = match measure {
+      ColumnMeasure::Encoding(ColumnEncoding::Utf8) => self.start_index + column_index,
+      _ => column_index,
+    };
+    let multi_byte_chars = &self.table.multi_byte_chars;
+    multi_byte_chars
+      .column_indexes(measure)
+      .partition_point(self.multi_byte_chars.clone(), |index| index < column_index)
+      .checked_sub(1)
+      .filter(|index| *index >= self.multi_byte_chars.start)
+      .map(|index| multi_byte_chars.get(index))
+  }
+
+  /// The column index of the character on the line in the provided measure.
+  fn char_column_index(&self, char_info: &MultiByteCharInfo, measure: ColumnMeasure) -> usize {
+    match measure {
+      ColumnMeasure::Encoding(ColumnEncoding::Utf8) => char_info.byte_index - self.start_index,
+      ColumnMeasure::Encoding(ColumnEncoding::Utf16) => char_info.line_utf16_index,
+      ColumnMeasure::Encoding(ColumnEncoding::Utf32 | ColumnEncoding::Graphemes) => {
+        char_info.line_char_index
+      }
+      ColumnMeasure::DisplayWidth => char_info.line_display_index,
+    }
+  }
+
+  /// Gets the column index of the byte index in the line, which may be
+  /// past the line's end index when it's in the newline.
+  pub fn column_index(&self, byte_index: usize, measure: ColumnMeasure) -> usize {
+    if let ColumnMeasure::Encoding(ColumnEncoding::Graphemes) = measure {
+      return self.grapheme_column_index(byte_index);
+    }
+
+    let utf8_measure = ColumnMeasure::Encoding(ColumnEncoding::Utf8);
+    match self
+      .last_multi_byte_char_before(byte_index.saturating_sub(self.start_index), utf8_measure)
+    {
+      Some(char_info) => {
+        let char_column_index = self.char_column_index(&char_info, measure);
+        let char_end_index = char_info.byte_index + char_info.length;
+        if byte_index < char_end_index {
+          char_column_index // in the middle of the char
+        } else {
+          char_column_index + char_info.column_length(measure) + byte_index - char_end_index
+        }
+      }
+      // could be before the line start when at the BOM position
+      None => byte_index.saturating_sub(self.start_index),
+    }
+  }
+
+  /// Gets the byte index of the column index in the line, falling back
+  /// to the line's end index when the column goes off.
+  pub fn byte_index(&self, column_index: usize, encoding: ColumnEncoding) -> usize {
+    let (column_index, encoding) = match encoding {
+      ColumnEncoding::Graphemes => (
+        self.grapheme_to_char_column_index(column_index),
+        ColumnEncoding::Utf32,
+      ),
+      _ => (column_index, encoding),
+    };
+    let measure = ColumnMeasure::Encoding(encoding);
+
+    let byte_index = match self.last_multi_byte_char_before(column_index, measure) {
+      Some(char_info) => {
+        let char_column_end_index =
+          self.char_column_index(&char_info, measure) + char_info.encoded_length(encoding);
+        if column_index < char_column_end_index {
+          char_info.byte_index // in the middle of the char
+        } else {
+          char_info.byte_index + char_info.length + column_index - char_column_end_index
+        }
+      }
+      None => self.start_index + column_index,
+    };
+    std::cmp::min(byte_index, self.end_index)
+  }
+
+  /// Gets the display column index of the byte index in the line where non-tab
+  /// characters take up columns based on the provided measure.
+  pub fn display_index(
+    &self,
+    byte_index: usize,
+    measure: ColumnMeasure,
+    indent_width: usize,
+    options: &TextLinesOptions,
+  ) -> usize {
+    let column_index = self.column_index(byte_index, measure);
+    let tab_chars = &self.table.tab_chars;
+    let tab_chars_end = tab_chars
+      .byte_indexes
+      .partition_point(self.tab_chars.clone(), |index| index < byte_index);
+    let tab_count = tab_chars_end - self.tab_chars.start;
+    let tab_char = match tab_chars_end.checked_sub(1) {
+      Some(tab_char_index) if tab_count > 0 => tab_chars.get(tab_char_index),
+      _ => return column_index,
+    };
+    match options.tab_display {
+      TabDisplay::FixedWidth => column_index - tab_count + tab_count * indent_width,
+      TabDisplay::TabStops
+        if matches!(measure, ColumnMeasure::DisplayWidth)
+          && indent_width == options.indent_width =>
+      {
+        // use the display index stored for the last tab
+        let tab_display_index = tab_char.line_display_index
+          + options
+            .tab_display
+            .tab_width(tab_char.line_display_index, indent_width);
+        tab_display_index + column_index - self.column_index(tab_char.byte_index, measure) - 1
+      }
+      TabDisplay::TabStops => {
+        let mut display_index = 0;
+        let mut last_column_index = 0;
+        for tab_char in self.tab_chars().take(tab_count) {
+          let tab_column_index = self.column_index(tab_char.byte_index, measure);
+          display_index += tab_column_index - last_column_index;
+          display_index += options.tab_display.tab_width(display_index, indent_width);
+          last_column_index = tab_column_index + 1;
+        }
+        display_index + column_index - last_column_index
+      }
+    }
+  }
+
+  fn grapheme_column_index(&self, byte_index: usize) -> usize {
+    // find the last grapheme cluster before the byte index
+    let grapheme_clusters = &self.table.grapheme_clusters;
+    let cluster_index = grapheme_clusters
+      .byte_indexes
+      .partition_point(self.grapheme_clusters.clone(), |index| index < byte_index)
+      .checked_sub(1)
+      .filter(|index| *index >= self.grapheme_clusters.start);
+    let char_measure = ColumnMeasure::Encoding(ColumnEncoding::Utf32);
+    match cluster_index {
+      Some(cluster_index) => {
+        let cluster = grapheme_clusters.get(cluster_index);
+        if byte_index < cluster.byte_index + cluster.length {
+          cluster.line_grapheme_index // in the middle of the grapheme cluster
+        } else {
+          let char_column_index = self.column_index(byte_index, char_measure);
+          cluster.line_grapheme_index + 1 + char_column_index
+            - cluster.line_char_index
+            - cluster.char_count
+        }
+      }
+      None => self.column_index(byte_index, char_measure),
+    }
+  }
+
+  /// Gets the character column index from the grapheme column index.
+  pub fn grapheme_to_char_column_index(&self, column_index: usize) -> usize {
+    // find the last grapheme cluster before the column index
+    let grapheme_clusters = &self.table.grapheme_clusters;
+    let cluster_index = grapheme_clusters
+      .line_grapheme_indexes
+      .partition_point(self.grapheme_clusters.clone(), |index| index < column_index)
+      .checked_sub(1)
+      .filter(|index| *index >= self.grapheme_clusters.start);
+    match cluster_index {
+      Some(cluster_index) => {
+        let cluster = grapheme_clusters.get(cluster_index);
+        cluster.line_char_index + cluster.char_count + column_index
+          - cluster.line_grapheme_index
+          - 1
+      }
+      None => column_index,
+    }
+  }
+
+  /// Gets the character index in the entire file of the provided byte index
+  /// within the line.
+  pub fn char_index(&self, byte_index: usize) -> usize {
+    self.char_start_index
+      + self.column_index(byte_index, ColumnMeasure::Encoding(ColumnEncoding::Utf32))
+  }
+
+  /// Gets the byte index of the provided character index in the entire
+  /// file, which must be within the line or its newline.
+  pub fn byte_index_from_char_index(&self, char_index: usize) -> usize {
+    let line_char_index = char_index - self.char_start_index;
+    let char_measure = ColumnMeasure::Encoding(ColumnEncoding::Utf32);
+    match self.last_multi_byte_char_before(line_char_index, char_measure) {
+      Some(char_info) => {
+        char_info.byte_index + char_info.length + line_char_index - char_info.line_char_index - 1
+      }
+      None => self.start_index + line_char_index,
+    }
+  }
+}
+
+/// A position in a line to start scanning from.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LinePosition {
+  /// The byte index in the entire file.
+  pub byte_index: usize,
+  pub line_start_index: usize,
+  /// The character index of the line start in the entire file.
+  pub line_char_start_index: usize,
+  pub line_char_index: usize,
+  pub line_utf16_index: usize,
+  /// The display column index on the line where tabs take up a single column.
+  pub line_display_index: usize,
+  /// The display column index on the line where tabs are expanded.
+  pub line_tab_display_index: usize,
+  pub line_grapheme_index: usize,
+}
+
+impl LinePosition {
+  /// Gets the position at the start of a line.
+  pub fn line_start(byte_index: usize, char_index: usize) -> Self {
+    LinePosition {
+      byte_index,
+      line_start_index: byte_index,
+      line_char_start_index: char_index,
+      ..Default::default()
+    }
+  }
+
+  /// Gets the position of the byte index in the line, which must be
+  /// the start of a grapheme cluster or the line's end index.
+  pub fn in_line(line: &TextLine, byte_index: usize, options: &TextLinesOptions) -> Self {
+    let display_measure = ColumnMeasure::DisplayWidth;
+    LinePosition {
+      byte_index,
+      line_start_index: line.start_index,
+      line_char_start_index: line.char_start_index,
+      line_char_index: line
+        .column_index(byte_index, ColumnMeasure::Encoding(ColumnEncoding::Utf32)),
+      line_utf16_index: line
+        .column_index(byte_index, ColumnMeasure::Encoding(ColumnEncoding::Utf16)),
+      line_display_index: line.column_index(byte_index, display_measure),
+      line_tab_display_index: line.display_index(
+        byte_index,
+        display_measure,
+        options.indent_width,
+        options,
+      ),
+      line_grapheme_index: line.column_index(
+        byte_index,
+        ColumnMeasure::Encoding(ColumnEncoding::Graphemes),
+      ),
+    }
+  }
+}
+
+/// Scans the lines of the text, which starts at the provided position in
+/// the entire text. The position must be the start of a grapheme cluster.
+///
+/// Each invalid UTF-8 sequence in the text is counted as a single
+/// replacement character.
+///
+/// Scanning stops before the line at the first line start index where
+/// `should_stop` returns true, which is then returned along with its
+/// character index.
+pub(crate) fn scan_lines(
+  text: &[u8],
+  start: LinePosition,
+  options: &TextLinesOptions,
+  mut should_stop: impl FnMut(usize) -> bool,
+) -> (LineTable, Option<(usize, usize)>) {
+  let line_terminators = options.line_terminators;
+  let text_index = start.byte_index;
+  let mut position = if text_index == 0 && text.starts_with(BOM_BYTES) {
+    // each byte of the BOM is counted as a character
+    LinePosition::line_start(BOM_CHAR.len_utf8(), BOM_CHAR.len_utf8())
+  } else {
+    start
+  };
+  // where to find the grapheme clusters of the current line from
+  let mut grapheme_start = position.clone();
+  let mut table = LineTable::default();
+  let include_vt_ff = line_terminators.is_other_terminator('\u{0B}');
+  let mut byte_index = position.byte_index - text_index;
+  loop {
+    // skip over runs of ASCII characters that each take up a single column
+    let special_index = ascii_scan::find_special_byte(text, byte_index, include_vt_ff);
+    if special_index > byte_index {
+      let run_length = special_index - byte_index;
+      position.line_char_index += run_length;
+      position.line_utf16_index += run_length;
+      position.line_display_index += run_length;
+      position.line_tab_display_index += run_length;
+      byte_index = special_index;
+    }
+    let (c, char_length) = match decode_char(text, byte_index) {
+      Some(decoded) => decoded,
+      None => break,
+    };
+    let display_width = if c == '\t' {
+      table.tab_chars.push(TabCharInfo {
+        byte_index: text_index + byte_index,
+        line_display_index: position.line_tab_display_index,
+      });
+      position.line_tab_display_index += options
+        .tab_display
+        .tab_width(position.line_tab_display_index, options.indent_width);
+      1
+    } else if c.len_utf8() > 1 {
+      // control characters aren't displayed
+      let display_width = c.width().unwrap_or(0);
+      // this includes multi-byte line terminators so that they're
+      // accounted for when getting the character index
+      table.multi_byte_chars.push(MultiByteCharInfo {
+        byte_index: text_index + byte_index,
+        line_char_index: position.line_char_index,
+        line_utf16_index: position.line_utf16_index,
+        line_display_index: position.line_display_index,
+        length: char_length,
+        display_width,
+      });
+      position.line_tab_display_index += display_width;
+      display_width
+    } else {
+      position.line_tab_display_index += 1;
+      1
+    };
+
+    let line_end_index = line_end_index(c, text, byte_index, line_terminators);
+    byte_index += char_length;
+    if let Some(end_index) = line_end_index {
+      table.push_line(
+        (text, text_index),
+        (
+          position.line_start_index,
+          text_index + end_index,
+          position.line_char_start_index,
+        ),
+        &grapheme_start,
+      );
+      position = LinePosition::line_start(
+        text_index + byte_index,
+        position.line_char_start_index + position.line_char_index + 1,
+      );
+      grapheme_start = position.clone();
+      if should_stop(position.byte_index) {
+        return (
+          table,
+          Some((position.line_start_index, position.line_char_start_index)),
+        );
+      }
+    } else {
+      position.line_char_index += 1;
+      position.line_utf16_index += c.len_utf16();
+      position.line_display_index += display_width;
+    }
+  }
+
+  table.push_line(
+    (text, text_index),
+    (
+      position.line_start_index,
+      text_index + text.len(),
+      position.line_char_start_index,
+    ),
+    &grapheme_start,
+  );
+  (table, None)
+}
+
+/// Gets the index that the line ends at when the character at the byte
+/// index terminates the line.
+pub(crate) fn line_end_index(
+  c: char,
+  bytes: &[u8],
+  byte_index: usize,
+  line_terminators: LineTerminators,
+) -> Option<usize> {
+  match c {
+    '\n'
+      if line_terminators != LineTerminators::Lf
+        && byte_index > 0
+        && bytes[byte_index - 1] == b'\r' =>
+    {
+      Some(byte_index - 1)
+    }
+    '\n' => Some(byte_index),
+    '\r' if line_terminators.is_lone_cr_terminator() => match bytes.get(byte_index + 1) {
+      Some(b'\n') => None, // handled on the \n
+      _ => Some(byte_index),
+    },
+    _ if line_terminators.is_other_terminator(c) => Some(byte_index),
+    _ => None,
+  }
+}
+
+/// Splits the bytes into runs of valid UTF-8 that are each followed by the
+/// length of the invalid sequence after it, which is zero at the end.
+///
+/// An invalid sequence is the longest prefix of a valid sequence, or a
+/// single byte, the same as with `String::from_utf8_lossy`.
+pub(crate) fn utf8_chunks(bytes: &[u8]) -> impl Iterator<Item = (&str, usize)> {
+  let mut bytes = bytes;
+  std::iter::from_fn(move || {
+    if bytes.is_empty() {
+      return None;
+    }
+    let (valid_text, invalid_length) = match std::str::from_utf8(bytes) {
+      Ok(text) => (text, 0),
+      Err(err) => {
+        let valid_length = err.valid_up_to();
+        (
+          std::str::from_utf8(&bytes[..valid_length]).unwrap(),
+          // the sequence is incomplete at the end of the bytes when none
+          err.error_len().unwrap_or(bytes.len() - valid_length),
+        )
+      }
+    };
+    bytes = &bytes[valid_text.len() + invalid_length..];
+    Some((valid_text, invalid_length))
+  })
+}
+
+/// Gets the character at the byte index along with its length in bytes,
+/// where an invalid UTF-8 sequence is a replacement character.
+fn decode_char(bytes: &[u8], byte_index: usize) -> Option<(char, usize)> {
+  let end_index = std::cmp::min(byte_index + 4, bytes.len());
+  let (valid_text, invalid_length) = utf8_chunks(&bytes[byte_index..end_index]).next()?;
+  match valid_text.chars().next() {
+    Some(c) => Some((c, c.len_utf8())),
+    None => Some((char::REPLACEMENT_CHARACTER, invalid_length)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn packed_vec_widens() {
+    let mut values = PackedVec::default();
+    values.push(1);
+    values.push(255);
+    assert!(matches!(values, PackedVec::U8(_)));
+    values.push(256);
+    assert!(matches!(values, PackedVec::U32(_)));
+    values.update(1.., |value| value + 1);
+    assert_eq!(values.iter().collect::<Vec<_>>(), vec![1, 256, 257]);
+    if usize::MAX > u32::MAX as usize {
+      values.splice(1..2, vec![u32::MAX as usize + 1].into_iter());
+      assert!(matches!(values, PackedVec::Usize(_)));
+      assert_eq!(
+        values.iter().collect::<Vec<_>>(),
+        vec![1, u32::MAX as usize + 1, 257]
+      );
+    }
+  }
+
+  #[test]
+  fn packed_vec_eq_ignores_width() {
+    let mut values = PackedVec::default();
+    values.push(300);
+    values.update(0.., |value| value - 299);
+    assert!(matches!(values, PackedVec::U32(_)));
+    let mut other = PackedVec::default();
+    other.push(1);
+    assert_eq!(values, other);
+    other.push(2);
+    assert_ne!(values, other);
+  }
+
+  #[test]
+  fn packed_vec_splice() {
+    let mut values = PackedVec::default();
+    values.splice(0..0, vec![1, 2, 3, 4].into_iter());
+    values.splice(1..3, vec![5].into_iter());
+    assert_eq!(values.iter().collect::<Vec<_>>(), vec![1, 5, 4]);
+    assert_eq!(values.partition_point(0..3, |value| value < 5), 1);
+    assert_eq!(values.partition_point(1..3, |value| value < 1), 1);
+  }
+}
@@ -0,0 +1,297 @@
+use std::ops::Range;
+
+use crate::LineAndColumnDisplay;
+use crate::LineAndColumnIndex;
+
+/// A range of byte indexes where the end is exclusive.
+#[cfg_attr(
+  feature = "serialization",
+  derive(serde::Serialize, serde::Deserialize)
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteRange {
+  pub start: usize,
+  pub end: usize,
+}
+
+impl ByteRange {
+  /// Gets the number of bytes in the range.
+  pub fn len(&self) -> usize {
+    self.end.saturating_sub(self.start)
+  }
+
+  /// Gets if the range doesn't contain any bytes.
+  pub fn is_empty(&self) -> bool {
+    is_empty(&self.start, &self.end)
+  }
+
+  /// Gets if the byte index is within the range.
+  pub fn contains(&self, byte_index: usize) -> bool {
+    contains(&self.start, &self.end, &byte_index)
+  }
+
+  /// Gets if the other range is entirely within this range.
+  pub fn contains_range(&self, other: ByteRange) -> bool {
+    contains_range((&self.start, &self.end), (&other.start, &other.end))
+  }
+
+  /// Gets the overlap of the ranges or `None` when they don't overlap.
+  ///
+  /// Ranges that only touch at their start and end have an empty overlap.
+  pub fn intersection(&self, other: ByteRange) -> Option<ByteRange> {
+    intersection((self.start, self.end), (other.start, other.end))
+      .map(|(start, end)| ByteRange { start, end })
+  }
+
+  /// Gets the smallest range that contains both ranges.
+  pub fn union(&self, other: ByteRange) -> ByteRange {
+    let (start, end) = union((self.start, self.end), (other.start, other.end));
+    ByteRange { start, end }
+  }
+}
+
+impl From<Range<usize>> for ByteRange {
+  fn from(range: Range<usize>) -> Self {
+    ByteRange {
+      start: range.start,
+      end: range.end,
+    }
+  }
+}
+
+impl From<ByteRange> for Range<usize> {
+  fn from(range: ByteRange) -> Self {
+    range.start..range.end
+  }
+}
+
+/// A range of line and column indexes where the end is exclusive.
+#[cfg_attr(
+  feature = "serialization",
+  derive(serde::Serialize, serde::Deserialize)
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineAndColumnRange {
+  pub start: LineAndColumnIndex,
+  pub end: LineAndColumnIndex,
+}
+
+impl LineAndColumnRange {
+  /// Gets if the range doesn't contain any positions.
+  pub fn is_empty(&self) -> bool {
+    is_empty(&self.start, &self.end)
+  }
+
+  /// Gets if the line and column is within the range.
+  pub fn contains(&self, line_and_column: LineAndColumnIndex) -> bool {
+    contains(&self.start, &self.end, &line_and_column)
+  }
+
+  /// Gets if the other range is entirely within this range.
+  pub fn contains_range(&self, other: LineAndColumnRange) -> bool {
+    contains_range((&self.start, &self.end), (&other.start, &other.end))
+  }
+
+  /// Gets the overlap of the ranges or `None` when they don't overlap.
+  ///
+  /// Ranges that only touch at their start and end have an empty overlap.
+  pub fn intersection(&self, other: LineAndColumnRange) -> Option<LineAndColumnRange> {
+    intersection((self.start, self.end), (other.start, other.end))
+      .map(|(start, end)| LineAndColumnRange { start, end })
+  }
+
+  /// Gets the smallest range that contains both ranges.
+  pub fn union(&self, other: LineAndColumnRange) -> LineAndColumnRange {
+    let (start, end) = union((self.start, self.end), (other.start, other.end));
+    LineAndColumnRange { start, end }
+  }
+}
+
+/// A range of line and column display positions where the end is exclusive.
+#[cfg_attr(
+  feature = "serialization",
+  derive(serde::Serialize, serde::Deserialize)
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DisplayRange {
+  pub start: LineAndColumnDisplay,
+  pub end: LineAndColumnDisplay,
+}
+
+impl DisplayRange {
+  /// Gets if the range doesn't contain any positions.
+  pub fn is_empty(&self) -> bool {
+    is_empty(&self.start, &self.end)
+  }
+
+  /// Gets if the line and column display is within the range.
+  pub fn contains(&self, line_and_column: LineAndColumnDisplay) -> bool {
+    contains(&self.start, &self.end, &line_and_column)
+  }
+
+  /// Gets if the other range is entirely within this range.
+  pub fn contains_range(&self, other: DisplayRange) -> bool {
+    contains_range((&self.start, &self.end), (&other.start, &other.end))
+  }
+
+  /// Gets the overlap of the ranges or `None` when they don't overlap.
+  ///
+  /// Ranges that only touch at their start and end have an empty overlap.
+  pub fn intersection(&self, other: DisplayRange) -> Option<DisplayRange> {
+    intersection((self.start, self.end), (other.start, other.end))
+      .map(|(start, end)| DisplayRange { start, end })
+  }
+
+  /// Gets the smallest range that contains both ranges.
+  pub fn union(&self, other: DisplayRange) -> DisplayRange {
+    let (start, end) = union((self.start, self.end), (other.start, other.end));
+    DisplayRange { start, end }
+  }
+}
+
+/// The part of a byte range on a single line.
+#[cfg_attr(
+  feature = "serialization",
+  derive(serde::Serialize, serde::Deserialize)
+)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LineSegment {
+  /// The zero-indexed line index.
+  pub line_index: usize,
+  /// The byte range of the segment, which never includes the newline.
+  pub byte_range: ByteRange,
+  /// The character column indexes of the segment on the line.
+  pub columns: Range<usize>,
+  /// The zero-indexed display columns of the segment on the line based on
+  /// the indentation width.
+  pub display_columns: Range<usize>,
+  /// If the range starts on this line.
+  pub starts_here: bool,
+  /// If the range ends on this line.
+  pub ends_here: bool,
+  /// If the range continues from the previous line onto the next line.
+  pub continues: bool,
+}
+
+fn is_empty<T: Ord>(start: &T, end: &T) -> bool {
+  start >= end
+}
+
+fn contains<T: Ord>(start: &T, end: &T, position: &T) -> bool {
+  start <= position && position < end
+}
+
+fn contains_range<T: Ord>((start, end): (&T, &T), (other_start, other_end): (&T, &T)) -> bool {
+  start <= other_start && other_end <= end
+}
+
+fn intersection<T: Ord>((start, end): (T, T), (other_start, other_end): (T, T)) -> Option<(T, T)> {
+  let start = std::cmp::max(start, other_start);
+  let end = std::cmp::min(end, other_end);
+  if start <= end {
+    Some((start, end))
+  } else {
+    None
+  }
+}
+
+fn union<T: Ord>((start, end): (T, T), (other_start, other_end): (T, T)) -> (T, T) {
+  (
+    std::cmp::min(start, other_start),
+    std::cmp::max(end, other_end),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn byte_range_operations() {
+    let range = ByteRange::from(2..6);
+    assert_eq!(range.len(), 4);
+    assert!(!range.is_empty());
+    assert!(ByteRange::from(3..3).is_empty());
+    assert!(range.contains(2));
+    assert!(range.contains(5));
+    assert!(!range.contains(6));
+    assert!(range.contains_range((2..6).into()));
+    assert!(range.contains_range((6..6).into()));
+    assert!(!range.contains_range((1..3).into()));
+    assert_eq!(range.intersection((4..8).into()), Some((4..6).into()));
+    assert_eq!(range.intersection((6..8).into()), Some((6..6).into()));
+    assert_eq!(range.intersection((7..8).into()), None);
+    assert_eq!(range.union((8..9).into()), (2..9).into());
+    assert_eq!(Range::from(range), 2..6);
+  }
+
+  #[test]
+  fn line_and_column_range_operations() {
+    let position = |line_index, column_index| LineAndColumnIndex {
+      line_index,
+      column_index,
+    };
+    let range = LineAndColumnRange {
+      start: position(0, 5),
+      end: position(2, 1),
+    };
+    assert!(range.contains(position(1, 0)));
+    assert!(range.contains(position(1, 100)));
+    assert!(!range.contains(position(0, 4)));
+    assert!(!range.contains(position(2, 1)));
+    let other = LineAndColumnRange {
+      start: position(1, 3),
+      end: position(3, 0),
+    };
+    assert_eq!(
+      range.intersection(other),
+      Some(LineAndColumnRange {
+        start: position(1, 3),
+        end: position(2, 1),
+      })
+    );
+    assert_eq!(
+      range.union(other),
+      LineAndColumnRange {
+        start: position(0, 5),
+        end: position(3, 0),
+      }
+    );
+
+    // sorts by line and then column
+    let mut positions = vec![position(1, 0), position(0, 7), position(0, 2)];
+    positions.sort();
+    assert_eq!(
+      positions,
+      vec![position(0, 2), position(0, 7), position(1, 0)]
+    );
+  }
+
+  #[test]
+  fn display_range_operations() {
+    let position = |line_number, column_number| LineAndColumnDisplay {
+      line_number,
+      column_number,
+    };
+    let range = DisplayRange {
+      start: position(1, 1),
+      end: position(1, 5),
+    };
+    let other = DisplayRange {
+      start: position(1, 8),
+      end: position(2, 1),
+    };
+    assert!(range.contains_range(DisplayRange {
+      start: position(1, 2),
+      end: position(1, 5),
+    }));
+    assert_eq!(range.intersection(other), None);
+    assert_eq!(
+      range.union(other),
+      DisplayRange {
+        start: position(1, 1),
+        end: position(2, 1),
+      }
+    );
+  }
+}
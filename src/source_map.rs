@@ -0,0 +1,1186 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::unwrap_or_panic;
+use crate::ColumnEncoding;
+use crate::LineAndColumnIndex;
+use crate::TextLines;
+use crate::TextLinesError;
+
+const BASE64_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const VLQ_CONTINUATION_BIT: u64 = 0b10_0000;
+/// The maximum nesting of JSON arrays and objects, which keeps untrusted
+/// input from overflowing the stack.
+const MAX_JSON_DEPTH: usize = 128;
+
+/// The position in an original source that a generated position maps to.
+#[cfg_attr(
+  feature = "serialization",
+  derive(serde::Serialize, serde::Deserialize)
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OriginalLocation {
+  /// The index of the source in the source map's sources.
+  pub source_index: usize,
+  /// The zero-indexed line and UTF-16 column in the source.
+  pub position: LineAndColumnIndex,
+  /// The index of the name in the source map's names.
+  pub name_index: Option<usize>,
+}
+
+/// A mapping from a generated position to an original position.
+#[cfg_attr(
+  feature = "serialization",
+  derive(serde::Serialize, serde::Deserialize)
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Mapping {
+  /// The zero-indexed line and UTF-16 column in the generated text.
+  pub generated: LineAndColumnIndex,
+  /// The original position or `None` when the generated text from this
+  /// position doesn't come from a source.
+  pub original: Option<OriginalLocation>,
+}
+
+/// An error from parsing a source map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceMapError {
+  /// The JSON was not valid at the byte index.
+  InvalidJson { byte_index: usize },
+  /// A field was missing or its value was not valid.
+  InvalidField { name: String },
+  /// The mappings were not valid at the byte index of the mappings.
+  InvalidMappings { byte_index: usize },
+}
+
+impl fmt::Display for SourceMapError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SourceMapError::InvalidJson { byte_index } => write!(
+        f,
+        "The source map was not valid JSON at byte index {}.",
+        byte_index
+      ),
+      SourceMapError::InvalidField { name } => write!(
+        f,
+        "The source map field \"{}\" was missing or not valid.",
+        name
+      ),
+      SourceMapError::InvalidMappings { byte_index } => write!(
+        f,
+        "The source map mappings were not valid at byte index {}.",
+        byte_index
+      ),
+    }
+  }
+}
+
+impl std::error::Error for SourceMapError {}
+
+/// A version 3 source map.
+///
+/// Lines and columns are zero-indexed and columns are measured in UTF-16
+/// code units as is done in practice. Use
+/// [`TextLines::line_and_column_index_with_encoding`] and
+/// [`TextLines::byte_index_with_encoding`] with [`ColumnEncoding::Utf16`]
+/// to convert them to and from byte indexes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+  /// The name of the generated file.
+  pub file: Option<String>,
+  /// The root that the sources are relative to.
+  pub source_root: Option<String>,
+  /// The names of the original sources.
+  pub sources: Vec<String>,
+  /// The text of each source, if included.
+  pub sources_content: Vec<Option<String>>,
+  /// The identifiers that mappings may refer to.
+  pub names: Vec<String>,
+  /// The mappings ordered by their generated position.
+  mappings: Vec<Mapping>,
+  /// The indexes of the mappings with an original position ordered by
+  /// the original position.
+  original_order: Vec<usize>,
+}
+
+impl SourceMap {
+  /// Creates a new `SourceMap` with the provided mappings, which are
+  /// ordered by their generated position.
+  pub(crate) fn with_mappings(mut mappings: Vec<Mapping>) -> Self {
+    mappings.sort_by_key(|mapping| mapping.generated);
+    let mut original_order = mappings
+      .iter()
+      .enumerate()
+      .filter(|(_, mapping)| mapping.original.is_some())
+      .map(|(index, _)| index)
+      .collect::<Vec<_>>();
+    // a stable sort keeps the mappings with the same original position in generated order
+    original_order.sort_by_key(|index| original_key(&mappings[*index]));
+    SourceMap {
+      mappings,
+      original_order,
+      ..Default::default()
+    }
+  }
+
+  /// Parses a source map from its JSON.
+  pub fn from_json(json: &str) -> Result<Self, SourceMapError> {
+    let value = JsonParser::new(json).parse()?;
+    let fields = match value {
+      JsonValue::Object(fields) => fields,
+      _ => return Err(SourceMapError::InvalidJson { byte_index: 0 }),
+    };
+    let field = |name: &str| {
+      fields
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value)
+    };
+    let invalid_field = |name: &str| SourceMapError::InvalidField {
+      name: name.to_string(),
+    };
+    let optional_string = |name: &str| match field(name) {
+      None | Some(JsonValue::Null) => Ok(None),
+      Some(JsonValue::String(value)) => Ok(Some(value.clone())),
+      Some(_) => Err(invalid_field(name)),
+    };
+    let strings = |name: &str| match field(name) {
+      None => Ok(Vec::new()),
+      Some(JsonValue::Array(values)) => values
+        .iter()
+        .map(|value| match value {
+          JsonValue::String(value) => Ok(Some(value.clone())),
+          JsonValue::Null => Ok(None),
+          _ => Err(invalid_field(name)),
+        })
+        .collect::<Result<Vec<_>, _>>(),
+      Some(_) => Err(invalid_field(name)),
+    };
+
+    match field("version") {
+      Some(JsonValue::Number(version)) if *version == 3.0 => {}
+      _ => return Err(invalid_field("version")),
+    }
+    // a null source is kept as an empty name so the indexes stay the same
+    let sources = strings("sources")?
+      .into_iter()
+      .map(|source| source.unwrap_or_default())
+      .collect::<Vec<_>>();
+    let names = strings("names")?
+      .into_iter()
+      .map(|name| name.ok_or_else(|| invalid_field("names")))
+      .collect::<Result<Vec<_>, _>>()?;
+    let sources_content = strings("sourcesContent")?;
+    if field("sourcesContent").is_some() && sources_content.len() != sources.len() {
+      return Err(invalid_field("sourcesContent"));
+    }
+    let mappings = match field("mappings") {
+      Some(JsonValue::String(mappings)) => decode_mappings(mappings, sources.len(), names.len())?,
+      _ => return Err(invalid_field("mappings")),
+    };
+
+    Ok(SourceMap {
+      file: optional_string("file")?,
+      source_root: optional_string("sourceRoot")?,
+      sources_content,
+      sources,
+      names,
+      ..SourceMap::with_mappings(mappings)
+    })
+  }
+
+  /// Gets the mappings ordered by their generated position.
+  pub fn mappings(&self) -> &[Mapping] {
+    &self.mappings
+  }
+
+  /// Gets the mapping for the provided generated position, which is the
+  /// closest mapping at or before it on the same line.
+  pub fn original_mapping(&self, generated: LineAndColumnIndex) -> Option<&Mapping> {
+    let index = self
+      .mappings
+      .partition_point(|mapping| mapping.generated <= generated)
+      .checked_sub(1)?;
+    let mapping = &self.mappings[index];
+    if mapping.generated.line_index == generated.line_index {
+      Some(mapping)
+    } else {
+      None
+    }
+  }
+
+  /// Gets the first generated mapping for the provided original position,
+  /// which is the closest original position at or before it on the same line.
+  pub fn generated_mapping(
+    &self,
+    source_index: usize,
+    original: LineAndColumnIndex,
+  ) -> Option<&Mapping> {
+    let key = (source_index, original);
+    let index = self
+      .original_order
+      .partition_point(|index| original_key(&self.mappings[*index]) <= key)
+      .checked_sub(1)?;
+    let found_key = original_key(&self.mappings[self.original_order[index]]);
+    if found_key.0 != source_index || found_key.1.line_index != original.line_index {
+      return None;
+    }
+    // get the first in generated order with the same original position
+    let index = self
+      .original_order
+      .partition_point(|index| original_key(&self.mappings[*index]) < found_key);
+    Some(&self.mappings[self.original_order[index]])
+  }
+
+  /// Gets the JSON of the source map.
+  pub fn to_json(&self) -> String {
+    let mut json = String::from("{\"version\":3");
+    if let Some(file) = &self.file {
+      json.push_str(",\"file\":");
+      write_json_string(&mut json, file);
+    }
+    if let Some(source_root) = &self.source_root {
+      json.push_str(",\"sourceRoot\":");
+      write_json_string(&mut json, source_root);
+    }
+    json.push_str(",\"sources\":");
+    write_json_strings(&mut json, self.sources.iter().map(Some));
+    if self.sources_content.iter().any(|content| content.is_some()) {
+      json.push_str(",\"sourcesContent\":");
+      write_json_strings(&mut json, self.sources_content.iter().map(Option::as_ref));
+    }
+    json.push_str(",\"names\":");
+    write_json_strings(&mut json, self.names.iter().map(Some));
+    json.push_str(",\"mappings\":\"");
+    // the mappings only contain base64 characters, commas and semicolons
+    json.push_str(&encode_mappings(&self.mappings));
+    json.push_str("\"}");
+    json
+  }
+}
+
+/// Builds a `SourceMap` from byte indexes in the generated text and the
+/// original sources.
+#[derive(Debug, Clone)]
+pub struct SourceMapBuilder<'a> {
+  generated: &'a TextLines,
+  sources: Vec<&'a TextLines>,
+  source_map: SourceMap,
+  mappings: Vec<Mapping>,
+  name_indexes: HashMap<String, usize>,
+}
+
+impl<'a> SourceMapBuilder<'a> {
+  /// Creates a new `SourceMapBuilder` with the lines of the generated text.
+  pub fn new(generated: &'a TextLines) -> Self {
+    SourceMapBuilder {
+      generated,
+      sources: Vec::new(),
+      source_map: SourceMap::default(),
+      mappings: Vec::new(),
+      name_indexes: HashMap::new(),
+    }
+  }
+
+  /// Sets the name of the generated file.
+  pub fn set_file(&mut self, file: impl Into<String>) {
+    self.source_map.file = Some(file.into());
+  }
+
+  /// Adds an original source with the lines of its text and optionally
+  /// the text to include in the source map, returning its source index.
+  pub fn add_source(
+    &mut self,
+    name: impl Into<String>,
+    lines: &'a TextLines,
+    content: Option<String>,
+  ) -> usize {
+    self.sources.push(lines);
+    self.source_map.sources.push(name.into());
+    self.source_map.sources_content.push(content);
+    self.sources.len() - 1
+  }
+
+  /// Adds a mapping from the generated byte index to the byte index in
+  /// the source with the provided index.
+  pub fn add_mapping(
+    &mut self,
+    generated_byte_index: usize,
+    source_index: usize,
+    byte_index: usize,
+  ) {
+    unwrap_or_panic(self.try_add_mapping(generated_byte_index, source_index, byte_index))
+  }
+
+  /// Fallible version of [`add_mapping`](Self::add_mapping).
+  pub fn try_add_mapping(
+    &mut self,
+    generated_byte_index: usize,
+    source_index: usize,
+    byte_index: usize,
+  ) -> Result<(), TextLinesError> {
+    self.push_mapping(generated_byte_index, Some((source_index, byte_index, None)))
+  }
+
+  /// Adds a mapping like [`add_mapping`](Self::add_mapping) that also
+  /// refers to the original name of the identifier at the position.
+  pub fn add_named_mapping(
+    &mut self,
+    generated_byte_index: usize,
+    source_index: usize,
+    byte_index: usize,
+    name: &str,
+  ) {
+    unwrap_or_panic(self.try_add_named_mapping(
+      generated_byte_index,
+      source_index,
+      byte_index,
+      name,
+    ))
+  }
+
+  /// Fallible version of [`add_named_mapping`](Self::add_named_mapping).
+  pub fn try_add_named_mapping(
+    &mut self,
+    generated_byte_index: usize,
+    source_index: usize,
+    byte_index: usize,
+    name: &str,
+  ) -> Result<(), TextLinesError> {
+    self.push_mapping(
+      generated_byte_index,
+      Some((source_index, byte_index, Some(name))),
+    )
+  }
+
+  /// Adds a mapping for generated text from the byte index that doesn't
+  /// come from a source.
+  pub fn add_unmapped(&mut self, generated_byte_index: usize) {
+    unwrap_or_panic(self.try_add_unmapped(generated_byte_index))
+  }
+
+  /// Fallible version of [`add_unmapped`](Self::add_unmapped).
+  pub fn try_add_unmapped(&mut self, generated_byte_index: usize) -> Result<(), TextLinesError> {
+    self.push_mapping(generated_byte_index, None)
+  }
+
+  /// Gets the `SourceMap` with the added sources and mappings.
+  pub fn finish(self) -> SourceMap {
+    SourceMap {
+      file: self.source_map.file,
+      source_root: self.source_map.source_root,
+      sources: self.source_map.sources,
+      sources_content: self.source_map.sources_content,
+      names: self.source_map.names,
+      ..SourceMap::with_mappings(self.mappings)
+    }
+  }
+
+  fn push_mapping(
+    &mut self,
+    generated_byte_index: usize,
+    original: Option<(usize, usize, Option<&str>)>,
+  ) -> Result<(), TextLinesError> {
+    let generated = self
+      .generated
+      .try_line_and_column_index_with_encoding(generated_byte_index, ColumnEncoding::Utf16)?;
+    let original = match original {
+      Some((source_index, byte_index, name)) => {
+        let lines =
+          self
+            .sources
+            .get(source_index)
+            .ok_or(TextLinesError::SourceIndexOutOfRange {
+              source_index,
+              sources_count: self.sources.len(),
+            })?;
+        Some(OriginalLocation {
+          source_index,
+          position: lines
+            .try_line_and_column_index_with_encoding(byte_index, ColumnEncoding::Utf16)?,
+          // only add the name once the positions are known to be valid
+          name_index: name.map(|name| self.name_index(name)),
+        })
+      }
+      None => None,
+    };
+    self.mappings.push(Mapping {
+      generated,
+      original,
+    });
+    Ok(())
+  }
+
+  fn name_index(&mut self, name: &str) -> usize {
+    match self.name_indexes.get(name) {
+      Some(name_index) => *name_index,
+      None => {
+        let name_index = self.source_map.names.len();
+        self.source_map.names.push(name.to_string());
+        self.name_indexes.insert(name.to_string(), name_index);
+        name_index
+      }
+    }
+  }
+}
+
+fn original_key(mapping: &Mapping) -> (usize, LineAndColumnIndex) {
+  let original = mapping.original.unwrap();
+  (original.source_index, original.position)
+}
+
+/// Encodes the mappings, which are ordered by their generated position.
+fn encode_mappings(mappings: &[Mapping]) -> String {
+  let mut encoded = String::new();
+  let mut line_index = 0;
+  let mut previous_column_index = 0;
+  let mut previous_source_index = 0;
+  let mut previous_original = LineAndColumnIndex {
+    line_index: 0,
+    column_index: 0,
+  };
+  let mut previous_name_index = 0;
+  for (i, mapping) in mappings.iter().enumerate() {
+    if mapping.generated.line_index > line_index || i == 0 {
+      for _ in line_index..mapping.generated.line_index {
+        encoded.push(';');
+      }
+      line_index = mapping.generated.line_index;
+      previous_column_index = 0;
+    } else {
+      encoded.push(',');
+    }
+    let mut write_relative = |value: usize, previous: &mut usize| {
+      encode_vlq(&mut encoded, value as i64 - *previous as i64);
+      *previous = value;
+    };
+    write_relative(mapping.generated.column_index, &mut previous_column_index);
+    if let Some(original) = &mapping.original {
+      write_relative(original.source_index, &mut previous_source_index);
+      write_relative(
+        original.position.line_index,
+        &mut previous_original.line_index,
+      );
+      write_relative(
+        original.position.column_index,
+        &mut previous_original.column_index,
+      );
+      if let Some(name_index) = original.name_index {
+        write_relative(name_index, &mut previous_name_index);
+      }
+    }
+  }
+  encoded
+}
+
+fn decode_mappings(
+  mappings: &str,
+  sources_count: usize,
+  names_count: usize,
+) -> Result<Vec<Mapping>, SourceMapError> {
+  let mut decoded = Vec::new();
+  let mut line_index = 0;
+  let mut column_index = 0;
+  // the source index, original line, original column and name index
+  let mut previous_values = [0; 4];
+  let bytes = mappings.as_bytes();
+  let mut byte_index = 0;
+  while byte_index < bytes.len() {
+    match bytes[byte_index] {
+      b';' => {
+        line_index += 1;
+        column_index = 0;
+        byte_index += 1;
+        continue;
+      }
+      b',' => {
+        byte_index += 1;
+        continue;
+      }
+      _ => {}
+    }
+
+    let segment_start = byte_index;
+    let invalid = || SourceMapError::InvalidMappings {
+      byte_index: segment_start,
+    };
+    let mut values = Vec::with_capacity(5);
+    while byte_index < bytes.len() && !matches!(bytes[byte_index], b';' | b',') {
+      values.push(decode_vlq(bytes, &mut byte_index)?);
+    }
+    let apply_relative = |previous: &mut usize, value: i64| {
+      *previous = (*previous as i64)
+        .checked_add(value)
+        .and_then(|value| usize::try_from(value).ok())
+        .ok_or_else(invalid)?;
+      Ok(*previous)
+    };
+    let generated = LineAndColumnIndex {
+      line_index,
+      column_index: apply_relative(&mut column_index, values[0])?,
+    };
+    let original = match values.len() {
+      1 => None,
+      4 | 5 => {
+        let source_index = apply_relative(&mut previous_values[0], values[1])?;
+        let original_line_index = apply_relative(&mut previous_values[1], values[2])?;
+        let original_column_index = apply_relative(&mut previous_values[2], values[3])?;
+        let name_index = match values.get(4) {
+          Some(value) => Some(apply_relative(&mut previous_values[3], *value)?),
+          None => None,
+        };
+        if source_index >= sources_count || name_index.is_some_and(|index| index >= names_count) {
+          return Err(invalid());
+        }
+        Some(OriginalLocation {
+          source_index,
+          position: LineAndColumnIndex {
+            line_index: original_line_index,
+            column_index: original_column_index,
+          },
+          name_index,
+        })
+      }
+      _ => return Err(invalid()),
+    };
+    decoded.push(Mapping {
+      generated,
+      original,
+    });
+  }
+  Ok(decoded)
+}
+
+/// Appends the value as a Base64 VLQ, where the lowest bit of the first
+/// digit is the sign.
+fn encode_vlq(output: &mut String, value: i64) {
+  let mut value = if value < 0 {
+    (value.unsigned_abs() << 1) | 1
+  } else {
+    (value as u64) << 1
+  };
+  loop {
+    let mut digit = value & (VLQ_CONTINUATION_BIT - 1);
+    value >>= 5;
+    if value > 0 {
+      digit |= VLQ_CONTINUATION_BIT;
+    }
+    output.push(BASE64_CHARS[digit as usize] as char);
+    if value == 0 {
+      break;
+    }
+  }
+}
+
+/// Decodes the Base64 VLQ at the byte index and moves the byte index past it.
+fn decode_vlq(bytes: &[u8], byte_index: &mut usize) -> Result<i64, SourceMapError> {
+  let start_index = *byte_index;
+  let mut value: u64 = 0;
+  let mut shift = 0;
+  loop {
+    let digit = bytes
+      .get(*byte_index)
+      .and_then(|byte| BASE64_CHARS.iter().position(|c| c == byte))
+      .ok_or(SourceMapError::InvalidMappings {
+        byte_index: *byte_index,
+      })? as u64;
+    let digit_value = digit & (VLQ_CONTINUATION_BIT - 1);
+    // the bits that would be shifted past the 64 bits of the value
+    if shift >= 64 || (shift > 0 && digit_value >> (64 - shift) != 0) {
+      return Err(SourceMapError::InvalidMappings {
+        byte_index: start_index,
+      });
+    }
+    value |= digit_value << shift;
+    shift += 5;
+    *byte_index += 1;
+    if digit & VLQ_CONTINUATION_BIT == 0 {
+      break;
+    }
+  }
+  let magnitude = (value >> 1) as i64;
+  Ok(if value & 1 == 1 {
+    -magnitude
+  } else {
+    magnitude
+  })
+}
+
+fn write_json_strings<'b>(json: &mut String, values: impl Iterator<Item = Option<&'b String>>) {
+  json.push('[');
+  for (i, value) in values.enumerate() {
+    if i > 0 {
+      json.push(',');
+    }
+    match value {
+      Some(value) => write_json_string(json, value),
+      None => json.push_str("null"),
+    }
+  }
+  json.push(']');
+}
+
+fn write_json_string(json: &mut String, value: &str) {
+  json.push('"');
+  for c in value.chars() {
+    match c {
+      '"' => json.push_str("\\\""),
+      '\\' => json.push_str("\\\\"),
+      '\n' => json.push_str("\\n"),
+      '\r' => json.push_str("\\r"),
+      '\t' => json.push_str("\\t"),
+      '\u{08}' => json.push_str("\\b"),
+      '\u{0C}' => json.push_str("\\f"),
+      c if c < ' ' => json.push_str(&format!("\\u{:04x}", c as u32)),
+      c => json.push(c),
+    }
+  }
+  json.push('"');
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum JsonValue {
+  Null,
+  Bool(bool),
+  Number(f64),
+  String(String),
+  Array(Vec<JsonValue>),
+  Object(Vec<(String, JsonValue)>),
+}
+
+struct JsonParser<'a> {
+  text: &'a str,
+  byte_index: usize,
+  depth: usize,
+}
+
+impl<'a> JsonParser<'a> {
+  fn new(text: &'a str) -> Self {
+    JsonParser {
+      text,
+      byte_index: 0,
+      depth: 0,
+    }
+  }
+
+  /// Parses the entire text as a single value.
+  fn parse(mut self) -> Result<JsonValue, SourceMapError> {
+    let value = self.parse_value()?;
+    self.skip_whitespace();
+    if self.byte_index < self.text.len() {
+      return Err(self.error());
+    }
+    Ok(value)
+  }
+
+  fn parse_value(&mut self) -> Result<JsonValue, SourceMapError> {
+    self.skip_whitespace();
+    match self.peek() {
+      Some(b'{') => self.parse_nested(Self::parse_object),
+      Some(b'[') => self.parse_nested(Self::parse_array),
+      Some(b'"') => Ok(JsonValue::String(self.parse_string()?)),
+      Some(b'-') | Some(b'0'..=b'9') => self.parse_number(),
+      Some(b't') => self.parse_literal("true", JsonValue::Bool(true)),
+      Some(b'f') => self.parse_literal("false", JsonValue::Bool(false)),
+      Some(b'n') => self.parse_literal("null", JsonValue::Null),
+      _ => Err(self.error()),
+    }
+  }
+
+  fn parse_nested(
+    &mut self,
+    parse: impl FnOnce(&mut Self) -> Result<JsonValue, SourceMapError>,
+  ) -> Result<JsonValue, SourceMapError> {
+    if self.depth == MAX_JSON_DEPTH {
+      return Err(self.error());
+    }
+    self.depth += 1;
+    let value = parse(self);
+    self.depth -= 1;
+    value
+  }
+
+  fn parse_object(&mut self) -> Result<JsonValue, SourceMapError> {
+    self.expect(b'{')?;
+    let mut fields = Vec::new();
+    self.skip_whitespace();
+    if self.peek() == Some(b'}') {
+      self.byte_index += 1;
+      return Ok(JsonValue::Object(fields));
+    }
+    loop {
+      self.skip_whitespace();
+      let key = self.parse_string()?;
+      self.skip_whitespace();
+      self.expect(b':')?;
+      let value = self.parse_value()?;
+      fields.push((key, value));
+      self.skip_whitespace();
+      match self.peek() {
+        Some(b',') => self.byte_index += 1,
+        Some(b'}') => {
+          self.byte_index += 1;
+          return Ok(JsonValue::Object(fields));
+        }
+        _ => return Err(self.error()),
+      }
+    }
+  }
+
+  fn parse_array(&mut self) -> Result<JsonValue, SourceMapError> {
+    self.expect(b'[')?;
+    let mut values = Vec::new();
+    self.skip_whitespace();
+    if self.peek() == Some(b']') {
+      self.byte_index += 1;
+      return Ok(JsonValue::Array(values));
+    }
+    loop {
+      values.push(self.parse_value()?);
+      self.skip_whitespace();
+      match self.peek() {
+        Some(b',') => self.byte_index += 1,
+        Some(b']') => {
+          self.byte_index += 1;
+          return Ok(JsonValue::Array(values));
+        }
+        _ => return Err(self.error()),
+      }
+    }
+  }
+
+  fn parse_string(&mut self) -> Result<String, SourceMapError> {
+    self.expect(b'"')?;
+    let mut value = String::new();
+    let mut run_start = self.byte_index;
+    loop {
+      match self.peek() {
+        Some(b'"') => {
+          value.push_str(&self.text[run_start..self.byte_index]);
+          self.byte_index += 1;
+          return Ok(value);
+        }
+        Some(b'\\') => {
+          value.push_str(&self.text[run_start..self.byte_index]);
+          self.byte_index += 1;
+          self.parse_escape(&mut value)?;
+          run_start = self.byte_index;
+        }
+        Some(byte) if byte >= b' ' => self.byte_index += 1,
+        _ => return Err(self.error()),
+      }
+    }
+  }
+
+  /// Parses the escape sequence after a backslash.
+  fn parse_escape(&mut self, value: &mut String) -> Result<(), SourceMapError> {
+    let c = match self.peek() {
+      Some(b'"') => '"',
+      Some(b'\\') => '\\',
+      Some(b'/') => '/',
+      Some(b'b') => '\u{08}',
+      Some(b'f') => '\u{0C}',
+      Some(b'n') => '\n',
+      Some(b'r') => '\r',
+      Some(b't') => '\t',
+      Some(b'u') => {
+        self.byte_index += 1;
+        let mut code_units = vec![self.parse_hex_code_unit()?];
+        // a surrogate pair is escaped as two code units
+        if (0xD800..0xDC00).contains(&code_units[0])
+          && self.text[self.byte_index..].starts_with("\\u")
+        {
+          self.byte_index += 2;
+          code_units.push(self.parse_hex_code_unit()?);
+        }
+        for c in char::decode_utf16(code_units) {
+          value.push(c.unwrap_or(char::REPLACEMENT_CHARACTER));
+        }
+        return Ok(());
+      }
+      _ => return Err(self.error()),
+    };
+    value.push(c);
+    self.byte_index += 1;
+    Ok(())
+  }
+
+  fn parse_hex_code_unit(&mut self) -> Result<u16, SourceMapError> {
+    let hex = self
+      .text
+      .get(self.byte_index..self.byte_index + 4)
+      .filter(|hex| hex.bytes().all(|byte| byte.is_ascii_hexdigit()))
+      .ok_or_else(|| self.error())?;
+    self.byte_index += 4;
+    Ok(u16::from_str_radix(hex, 16).unwrap())
+  }
+
+  fn parse_number(&mut self) -> Result<JsonValue, SourceMapError> {
+    let start_index = self.byte_index;
+    // parsing an f64 accepts more than JSON does, such as `+1`, `.5`
+    // and `inf`, so the number is checked against the JSON grammar first
+    if self.peek() == Some(b'-') {
+      self.byte_index += 1;
+    }
+    // the integer part only starts with a zero when it is zero
+    if self.peek() == Some(b'0') {
+      self.byte_index += 1;
+    } else {
+      self.skip_digits()?;
+    }
+    if self.peek() == Some(b'.') {
+      self.byte_index += 1;
+      self.skip_digits()?;
+    }
+    if let Some(b'e' | b'E') = self.peek() {
+      self.byte_index += 1;
+      if let Some(b'+' | b'-') = self.peek() {
+        self.byte_index += 1;
+      }
+      self.skip_digits()?;
+    }
+    match self.text[start_index..self.byte_index].parse() {
+      Ok(number) => Ok(JsonValue::Number(number)),
+      Err(_) => Err(SourceMapError::InvalidJson {
+        byte_index: start_index,
+      }),
+    }
+  }
+
+  /// Skips one or more digits.
+  fn skip_digits(&mut self) -> Result<(), SourceMapError> {
+    if !matches!(self.peek(), Some(b'0'..=b'9')) {
+      return Err(self.error());
+    }
+    while let Some(b'0'..=b'9') = self.peek() {
+      self.byte_index += 1;
+    }
+    Ok(())
+  }
+
+  fn parse_literal(
+    &mut self,
+    literal: &str,
+    value: JsonValue,
+  ) -> Result<JsonValue, SourceMapError> {
+    if self.text[self.byte_index..].starts_with(literal) {
+      self.byte_index += literal.len();
+      Ok(value)
+    } else {
+      Err(self.error())
+    }
+  }
+
+  fn expect(&mut self, byte: u8) -> Result<(), SourceMapError> {
+    if self.peek() == Some(byte) {
+      self.byte_index += 1;
+      Ok(())
+    } else {
+      Err(self.error())
+    }
+  }
+
+  fn skip_whitespace(&mut self) {
+    while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+      self.byte_index += 1;
+    }
+  }
+
+  fn peek(&self) -> Option<u8> {
+    self.text.as_bytes().get(self.byte_index).copied()
+  }
+
+  fn error(&self) -> SourceMapError {
+    SourceMapError::InvalidJson {
+      byte_index: self.byte_index,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn position(line_index: usize, column_index: usize) -> LineAndColumnIndex {
+    LineAndColumnIndex {
+      line_index,
+      column_index,
+    }
+  }
+
+  #[test]
+  fn vlq() {
+    for (value, expected) in [
+      (0, "A"),
+      (1, "C"),
+      (-1, "D"),
+      (15, "e"),
+      (16, "gB"),
+      (123, "2H"),
+      (-123456789, "rxmvrH"),
+    ] {
+      let mut encoded = String::new();
+      encode_vlq(&mut encoded, value);
+      assert_eq!(encoded, expected);
+      let mut byte_index = 0;
+      assert_eq!(decode_vlq(encoded.as_bytes(), &mut byte_index), Ok(value));
+      assert_eq!(byte_index, encoded.len());
+    }
+    let mut byte_index = 0;
+    assert_eq!(
+      decode_vlq(b"g", &mut byte_index),
+      Err(SourceMapError::InvalidMappings { byte_index: 1 })
+    );
+    // the 13th digit only has the last 4 of the 64 bits left
+    let mut byte_index = 0;
+    assert_eq!(decode_vlq(b"ggggggggggggC", &mut byte_index), Ok(1 << 60));
+    let mut byte_index = 0;
+    assert_eq!(
+      decode_vlq(b"ggggggggggggf", &mut byte_index),
+      Err(SourceMapError::InvalidMappings { byte_index: 0 })
+    );
+  }
+
+  #[test]
+  fn build_and_parse() {
+    let original_text = "let 😀 = a;\nfoo(😀);";
+    let generated_text = "(function() {\n  var x = a; foo(x);\n})();";
+    let original = TextLines::new(original_text);
+    let generated = TextLines::new(generated_text);
+    let mut builder = SourceMapBuilder::new(&generated);
+    builder.set_file("out.js");
+    let source_index = builder.add_source("in.js", &original, Some(original_text.to_string()));
+    builder.add_unmapped(0);
+    // "x" maps to the emoji, which is two UTF-16 code units
+    builder.add_mapping(16, source_index, 0);
+    builder.add_named_mapping(20, source_index, 4, "😀");
+    builder.add_mapping(24, source_index, 11);
+    builder.add_mapping(27, source_index, 14);
+    builder.add_named_mapping(31, source_index, 18, "😀");
+    let source_map = builder.finish();
+
+    let json = source_map.to_json();
+    assert_eq!(
+      json,
+      concat!(
+        r#"{"version":3,"file":"out.js","sources":["in.js"],"#,
+        r#""sourcesContent":["let 😀 = a;\nfoo(😀);"],"names":["😀"],"#,
+        r#""mappings":"A;EAAA,IAAIA,IAAK,GACT,IAAIA"}"#,
+      )
+    );
+    assert_eq!(SourceMap::from_json(&json).unwrap(), source_map);
+
+    let mapping = source_map.original_mapping(position(1, 6)).unwrap();
+    assert_eq!(
+      mapping.original,
+      Some(OriginalLocation {
+        source_index,
+        position: position(0, 4),
+        name_index: Some(0),
+      })
+    );
+    assert_eq!(
+      original.byte_index_with_encoding(mapping.original.unwrap().position, ColumnEncoding::Utf16),
+      4
+    );
+    assert_eq!(
+      source_map
+        .original_mapping(position(0, 5))
+        .unwrap()
+        .original,
+      None
+    );
+    assert_eq!(source_map.original_mapping(position(2, 0)), None);
+
+    let mapping = source_map
+      .generated_mapping(source_index, position(1, 5))
+      .unwrap();
+    assert_eq!(mapping.generated, position(1, 17));
+    let mapping = source_map
+      .generated_mapping(source_index, position(0, 0))
+      .unwrap();
+    assert_eq!(mapping.generated, position(1, 2));
+    assert_eq!(
+      source_map.generated_mapping(source_index, position(2, 0)),
+      None
+    );
+    assert_eq!(source_map.generated_mapping(1, position(0, 0)), None);
+  }
+
+  #[test]
+  fn builder_errors() {
+    let generated = TextLines::new("ab");
+    let original = TextLines::new("a");
+    let mut builder = SourceMapBuilder::new(&generated);
+    let source_index = builder.add_source("a.js", &original, None);
+    assert_eq!(
+      builder.try_add_mapping(0, 5, 0),
+      Err(TextLinesError::SourceIndexOutOfRange {
+        source_index: 5,
+        sources_count: 1,
+      })
+    );
+    assert_eq!(
+      builder.try_add_mapping(3, source_index, 0),
+      Err(TextLinesError::ByteIndexOutOfRange {
+        byte_index: 3,
+        text_length: 2,
+      })
+    );
+    assert_eq!(
+      builder.try_add_named_mapping(0, source_index, 2, "a"),
+      Err(TextLinesError::ByteIndexOutOfRange {
+        byte_index: 2,
+        text_length: 1,
+      })
+    );
+    let source_map = builder.finish();
+    assert!(source_map.mappings().is_empty());
+    assert!(source_map.names.is_empty());
+  }
+
+  #[test]
+  fn parse() {
+    let json = r#"
+      {
+        "version": 3,
+        "sourceRoot": "src/é😀\"",
+        "sources": ["a.js", null],
+        "names": [],
+        "mappings": ";;AAAA,CAAC;ACAA",
+        "x_extension": [true, false, null, {"a": -1.5e2}]
+      }
+    "#;
+    let source_map = SourceMap::from_json(json).unwrap();
+    assert_eq!(source_map.source_root.as_deref(), Some("src/é😀\""));
+    assert_eq!(source_map.sources, vec!["a.js".to_string(), String::new()]);
+    assert!(source_map.sources_content.is_empty());
+    assert_eq!(
+      source_map.mappings(),
+      &[
+        Mapping {
+          generated: position(2, 0),
+          original: Some(OriginalLocation {
+            source_index: 0,
+            position: position(0, 0),
+            name_index: None,
+          }),
+        },
+        Mapping {
+          generated: position(2, 1),
+          original: Some(OriginalLocation {
+            source_index: 0,
+            position: position(0, 1),
+            name_index: None,
+          }),
+        },
+        Mapping {
+          generated: position(3, 0),
+          original: Some(OriginalLocation {
+            source_index: 1,
+            position: position(0, 1),
+            name_index: None,
+          }),
+        },
+      ]
+    );
+    let reparsed = SourceMap::from_json(&source_map.to_json()).unwrap();
+    assert_eq!(reparsed, source_map);
+  }
+
+  #[test]
+  fn parse_errors() {
+    let with_mappings = |mappings: &str| {
+      SourceMap::from_json(&format!(
+        r#"{{"version":3,"sources":["a.js"],"names":[],"mappings":"{}"}}"#,
+        mappings
+      ))
+    };
+    assert_eq!(
+      with_mappings("AA"),
+      Err(SourceMapError::InvalidMappings { byte_index: 0 })
+    );
+    assert_eq!(
+      with_mappings("A;ACAA"),
+      Err(SourceMapError::InvalidMappings { byte_index: 2 })
+    );
+    assert_eq!(
+      with_mappings("AAAA,D"),
+      Err(SourceMapError::InvalidMappings { byte_index: 5 })
+    );
+    assert_eq!(
+      with_mappings("ggggggggggggI,ggggggggggggI"),
+      Err(SourceMapError::InvalidMappings { byte_index: 14 })
+    );
+    assert_eq!(
+      with_mappings("A!"),
+      Err(SourceMapError::InvalidMappings { byte_index: 1 })
+    );
+    assert_eq!(
+      SourceMap::from_json(r#"{"version":2,"sources":[],"mappings":""}"#),
+      Err(SourceMapError::InvalidField {
+        name: "version".to_string(),
+      })
+    );
+    assert_eq!(
+      SourceMap::from_json(r#"{"version":3,"sources":[]}"#),
+      Err(SourceMapError::InvalidField {
+        name: "mappings".to_string(),
+      })
+    );
+    assert_eq!(
+      SourceMap::from_json(r#"{"version":3,"sources":["a.js"],"sourcesContent":[],"mappings":""}"#),
+      Err(SourceMapError::InvalidField {
+        name: "sourcesContent".to_string(),
+      })
+    );
+    assert_eq!(
+      SourceMap::from_json(r#"{"version":3,}"#),
+      Err(SourceMapError::InvalidJson { byte_index: 13 })
+    );
+    for (version, byte_index) in [
+      ("+3", 11),
+      ("03", 12),
+      ("3.", 13),
+      (".5", 11),
+      ("-", 12),
+      ("3e", 13),
+      ("inf", 11),
+      ("NaN", 11),
+    ] {
+      assert_eq!(
+        SourceMap::from_json(&format!(r#"{{"version":{}}}"#, version)),
+        Err(SourceMapError::InvalidJson { byte_index }),
+        "Version: {}",
+        version
+      );
+    }
+    let nested = format!(
+      r#"{{"version":3,"sources":[],"mappings":"","x":{}{}}}"#,
+      "[".repeat(200_000),
+      "]".repeat(200_000)
+    );
+    assert_eq!(
+      SourceMap::from_json(&nested),
+      Err(SourceMapError::InvalidJson { byte_index: 171 })
+    );
+    assert_eq!(
+      SourceMap::from_json("{} {}").unwrap_err().to_string(),
+      "The source map was not valid JSON at byte index 3."
+    );
+  }
+
+  #[test]
+  fn json_string_escapes() {
+    let mut json = String::new();
+    write_json_string(&mut json, "a\"\\\n\r\t\u{08}\u{0C}\u{01}é");
+    assert_eq!(json, r#""a\"\\\n\r\t\b\f\u0001é""#);
+    assert_eq!(
+      JsonParser::new(&json).parse(),
+      Ok(JsonValue::String(
+        "a\"\\\n\r\t\u{08}\u{0C}\u{01}é".to_string()
+      ))
+    );
+  }
+}
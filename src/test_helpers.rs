@@ -0,0 +1,42 @@
+use crate::LineTerminators;
+use crate::TabDisplay;
+use crate::TextLines;
+use crate::TextLinesOptions;
+
+/// Texts with BOMs, multi-byte characters, grapheme clusters, tabs and
+/// each kind of line terminator for checking other ways of getting line
+/// information against `TextLines`.
+pub(crate) const ORACLE_TEXTS: &[&str] = &[
+  "",
+  "\u{FEFF}",
+  "\u{FEFF}β1\n😀\r\n",
+  "12\n3\r\n4\n5",
+  "β1β\nΔβ1\r\nt\nu",
+  "\n\n\r\n\tββ\n",
+  "\t中\u{0301}\t🇺🇸a\r\r\n😀",
+  "\u{FEFF}a\r\nβ\r\r\n😀e\u{0301}\t中\r",
+  "a\u{2028}β\u{85}\u{85}\r😀\r\n\r\u{0B}\u{0C}",
+];
+
+const ALL_LINE_TERMINATORS: [LineTerminators; 5] = [
+  LineTerminators::Lf,
+  LineTerminators::LfAndCrLf,
+  LineTerminators::Universal,
+  LineTerminators::EcmaScript,
+  LineTerminators::Unicode,
+];
+
+/// Calls `f` with each of the oracle texts along with the options and
+/// `TextLines` of the text for each kind of line terminator.
+pub(crate) fn for_each_oracle(mut f: impl FnMut(&str, TextLinesOptions, &TextLines)) {
+  for text in ORACLE_TEXTS {
+    for line_terminators in ALL_LINE_TERMINATORS {
+      let options = TextLinesOptions {
+        line_terminators,
+        tab_display: TabDisplay::TabStops,
+        ..Default::default()
+      };
+      f(text, options, &TextLines::with_options(text, options));
+    }
+  }
+}
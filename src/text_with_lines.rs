@@ -0,0 +1,170 @@
+use std::ops::Deref;
+use std::ops::Range;
+
+use crate::TextLines;
+use crate::TextLinesError;
+use crate::TextLinesOptions;
+
+/// Text along with information about its lines.
+///
+/// The text may be borrowed (ex. `&str`) or owned (ex. `String` or `Arc<str>`)
+/// and all the methods of `TextLines` are available via `Deref`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextWithLines<T: AsRef<str>> {
+  text: T,
+  lines: TextLines,
+}
+
+impl<T: AsRef<str>> TextWithLines<T> {
+  /// Creates a new `TextWithLines` with the specified text and default
+  /// indent width of 4.
+  pub fn new(text: T) -> Self {
+    TextWithLines::with_options(text, Default::default())
+  }
+
+  /// Creates a new `TextWithLines` with the specified text and options.
+  pub fn with_options(text: T, options: TextLinesOptions) -> Self {
+    let lines = TextLines::with_options(text.as_ref(), options);
+    TextWithLines { text, lines }
+  }
+
+  /// Gets the text.
+  pub fn text(&self) -> &str {
+    self.text.as_ref()
+  }
+
+  /// Gets the information about the lines of the text.
+  pub fn text_lines(&self) -> &TextLines {
+    &self.lines
+  }
+
+  /// Takes the text.
+  pub fn into_text(self) -> T {
+    self.text
+  }
+
+  /// Gets the text of the line without the newline.
+  pub fn line_text(&self, line_index: usize) -> &str {
+    crate::unwrap_or_panic(self.try_line_text(line_index))
+  }
+
+  /// Fallible version of [`line_text`](Self::line_text).
+  pub fn try_line_text(&self, line_index: usize) -> Result<&str, TextLinesError> {
+    let (start, end) = self.lines.try_line_range(line_index)?;
+    Ok(&self.text()[start..end])
+  }
+
+  /// Gets the text of the line including its newline, if any.
+  pub fn line_text_with_terminator(&self, line_index: usize) -> &str {
+    crate::unwrap_or_panic(self.try_line_text_with_terminator(line_index))
+  }
+
+  /// Fallible version of [`line_text_with_terminator`](Self::line_text_with_terminator).
+  pub fn try_line_text_with_terminator(&self, line_index: usize) -> Result<&str, TextLinesError> {
+    let start = self.lines.try_line_start(line_index)?;
+    let end = if line_index + 1 < self.lines.lines_count() {
+      self.lines.line_start(line_index + 1)
+    } else {
+      self.text().len()
+    };
+    Ok(&self.text()[start..end])
+  }
+
+  /// Gets the text in the provided byte range.
+  pub fn text_in_range(&self, range: Range<usize>) -> &str {
+    crate::unwrap_or_panic(self.try_text_in_range(range))
+  }
+
+  /// Fallible version of [`text_in_range`](Self::text_in_range).
+  pub fn try_text_in_range(&self, range: Range<usize>) -> Result<&str, TextLinesError> {
+    if range.start > range.end {
+      return Err(TextLinesError::InvalidRange {
+        start: range.start,
+        end: range.end,
+      });
+    }
+    let text = self.text();
+    for byte_index in [range.start, range.end] {
+      if byte_index > text.len() {
+        return Err(TextLinesError::ByteIndexOutOfRange {
+          byte_index,
+          text_length: text.len(),
+        });
+      }
+      if !text.is_char_boundary(byte_index) {
+        return Err(TextLinesError::NotCharBoundary { byte_index });
+      }
+    }
+    Ok(&text[range])
+  }
+
+  /// Gets an iterator over the text of each line without the newlines.
+  pub fn lines(&self) -> impl Iterator<Item = &str> + '_ {
+    (0..self.lines.lines_count()).map(move |line_index| self.line_text(line_index))
+  }
+}
+
+impl<T: AsRef<str>> Deref for TextWithLines<T> {
+  type Target = TextLines;
+
+  fn deref(&self) -> &TextLines {
+    &self.lines
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use super::*;
+
+  #[test]
+  fn line_text() {
+    let text = TextWithLines::new("\u{FEFF}1\n23\r\n\n4");
+    assert_eq!(text.line_text(0), "1");
+    assert_eq!(text.line_text(1), "23");
+    assert_eq!(text.line_text(2), "");
+    assert_eq!(text.line_text(3), "4");
+    assert_eq!(
+      text.try_line_text(4),
+      Err(TextLinesError::LineIndexOutOfRange {
+        line_index: 4,
+        lines_count: 4,
+      })
+    );
+  }
+
+  #[test]
+  fn line_text_with_terminator() {
+    let text = TextWithLines::new(String::from("\u{FEFF}1\n23\r\n\n4"));
+    assert_eq!(text.line_text_with_terminator(0), "1\n");
+    assert_eq!(text.line_text_with_terminator(1), "23\r\n");
+    assert_eq!(text.line_text_with_terminator(2), "\n");
+    assert_eq!(text.line_text_with_terminator(3), "4");
+  }
+
+  #[test]
+  fn text_in_range() {
+    let text = TextWithLines::new(Arc::<str>::from("1β\n2"));
+    assert_eq!(text.text_in_range(1..5), "β\n2");
+    assert_eq!(
+      text.try_text_in_range(2..5),
+      Err(TextLinesError::NotCharBoundary { byte_index: 2 })
+    );
+    assert_eq!(
+      text.try_text_in_range(0..6),
+      Err(TextLinesError::ByteIndexOutOfRange {
+        byte_index: 6,
+        text_length: 5,
+      })
+    );
+  }
+
+  #[test]
+  fn lines() {
+    let text = TextWithLines::new("1\r\n2\n\n");
+    assert_eq!(text.lines().collect::<Vec<_>>(), vec!["1", "2", "", ""]);
+    assert_eq!(text.lines_count(), 4);
+    assert_eq!(text.line_and_column_index(3).line_index, 1);
+  }
+}
@@ -0,0 +1,413 @@
+use crate::line_table::PackedVec;
+use crate::unwrap_or_panic;
+use crate::ColumnEncoding;
+use crate::ColumnMeasure;
+use crate::LineAndColumnDisplay;
+use crate::LineAndColumnIndex;
+use crate::TextLines;
+use crate::TextLinesError;
+use crate::TextLinesOptions;
+
+/// The number of bytes of UTF-8 text to transcode before adding it.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Information about the lines of a text made of UTF-16 code units, where
+/// indexes are UTF-16 code unit indexes.
+///
+/// The text is transcoded to UTF-8 in chunks to get the `TextLines` for it,
+/// so the byte indexes of the UTF-8 text can also be converted to and from.
+/// An unpaired surrogate is counted as a single replacement character
+/// (`U+FFFD`), which is also one UTF-16 code unit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Utf16TextLines {
+  lines: TextLines,
+  line_utf16_starts: PackedVec,
+  text_length: usize,
+}
+
+impl Utf16TextLines {
+  /// Creates a new `Utf16TextLines` with the specified text and default
+  /// indent width of 4.
+  pub fn new(text: &[u16]) -> Self {
+    Utf16TextLines::with_options(text, Default::default())
+  }
+
+  /// Creates a new `Utf16TextLines` with the specified text and options.
+  pub fn with_options(text: &[u16], options: TextLinesOptions) -> Self {
+    let mut lines = TextLines::with_options("", options);
+    let mut chunk = String::with_capacity(CHUNK_SIZE + 4);
+    for c in char::decode_utf16(text.iter().copied()) {
+      chunk.push(c.unwrap_or(char::REPLACEMENT_CHARACTER));
+      if chunk.len() >= CHUNK_SIZE {
+        lines.append(&chunk);
+        chunk.clear();
+      }
+    }
+    lines.append(&chunk);
+
+    // the BOM is a single code unit, which is before the first line
+    let mut line_utf16_start = if lines.line_start(0) > 0 { 1 } else { 0 };
+    let mut line_utf16_starts = PackedVec::default();
+    line_utf16_starts.push(line_utf16_start);
+    for line_index in 1..lines.lines_count() {
+      // the previous line's newline is included in its columns
+      line_utf16_start += lines.lines.line(line_index - 1).column_index(
+        lines.line_start(line_index),
+        ColumnMeasure::Encoding(ColumnEncoding::Utf16),
+      );
+      line_utf16_starts.push(line_utf16_start);
+    }
+    line_utf16_starts.shrink_to_fit();
+
+    Utf16TextLines {
+      lines,
+      line_utf16_starts,
+      text_length: text.len(),
+    }
+  }
+
+  /// Gets the information about the lines of the text transcoded to UTF-8.
+  pub fn text_lines(&self) -> &TextLines {
+    &self.lines
+  }
+
+  /// Gets the number of lines in the text.
+  pub fn lines_count(&self) -> usize {
+    self.lines.lines_count()
+  }
+
+  /// Gets the text length in UTF-16 code units.
+  pub fn text_length(&self) -> usize {
+    self.text_length
+  }
+
+  /// Gets the line index from a UTF-16 index.
+  /// Note that if you provide the middle index of a \r\n newline
+  /// then it will return the index of the preceding line.
+  pub fn line_index(&self, utf16_index: usize) -> usize {
+    unwrap_or_panic(self.try_line_index(utf16_index))
+  }
+
+  /// Fallible version of [`line_index`](Self::line_index).
+  pub fn try_line_index(&self, utf16_index: usize) -> Result<usize, TextLinesError> {
+    self.validate_utf16_index(utf16_index)?;
+    Ok(self.line_utf16_starts.line_index_of(utf16_index))
+  }
+
+  /// Gets the line start UTF-16 index.
+  pub fn line_start(&self, line_index: usize) -> usize {
+    unwrap_or_panic(self.try_line_start(line_index))
+  }
+
+  /// Fallible version of [`line_start`](Self::line_start).
+  pub fn try_line_start(&self, line_index: usize) -> Result<usize, TextLinesError> {
+    self.lines.try_line_start(line_index)?;
+    Ok(self.line_utf16_starts.get(line_index))
+  }
+
+  /// Gets the line end UTF-16 index (before the newline character).
+  pub fn line_end(&self, line_index: usize) -> usize {
+    unwrap_or_panic(self.try_line_end(line_index))
+  }
+
+  /// Fallible version of [`line_end`](Self::line_end).
+  pub fn try_line_end(&self, line_index: usize) -> Result<usize, TextLinesError> {
+    let line_end = self.lines.try_line_end(line_index)?;
+    Ok(self.utf16_index_in_line(line_index, line_end))
+  }
+
+  /// Gets the line range in UTF-16 indexes.
+  pub fn line_range(&self, line_index: usize) -> (usize, usize) {
+    unwrap_or_panic(self.try_line_range(line_index))
+  }
+
+  /// Fallible version of [`line_range`](Self::line_range).
+  pub fn try_line_range(&self, line_index: usize) -> Result<(usize, usize), TextLinesError> {
+    Ok((
+      self.try_line_start(line_index)?,
+      self.try_line_end(line_index)?,
+    ))
+  }
+
+  /// Gets the UTF-16 index from the provided line and column index.
+  pub fn utf16_index(&self, line_and_column: LineAndColumnIndex) -> usize {
+    self.utf16_index_with_encoding(line_and_column, ColumnEncoding::Utf32)
+  }
+
+  /// Fallible version of [`utf16_index`](Self::utf16_index).
+  pub fn try_utf16_index(
+    &self,
+    line_and_column: LineAndColumnIndex,
+  ) -> Result<usize, TextLinesError> {
+    self.try_utf16_index_with_encoding(line_and_column, ColumnEncoding::Utf32)
+  }
+
+  /// Gets the UTF-16 index from the provided line and column index where
+  /// the column is measured in the provided encoding.
+  pub fn utf16_index_with_encoding(
+    &self,
+    line_and_column: LineAndColumnIndex,
+    encoding: ColumnEncoding,
+  ) -> usize {
+    unwrap_or_panic(self.try_utf16_index_with_encoding(line_and_column, encoding))
+  }
+
+  /// Fallible version of [`utf16_index_with_encoding`](Self::utf16_index_with_encoding).
+  pub fn try_utf16_index_with_encoding(
+    &self,
+    line_and_column: LineAndColumnIndex,
+    encoding: ColumnEncoding,
+  ) -> Result<usize, TextLinesError> {
+    let byte_index = self
+      .lines
+      .try_byte_index_with_encoding(line_and_column, encoding)?;
+    Ok(self.utf16_index_in_line(line_and_column.line_index, byte_index))
+  }
+
+  /// Gets the line and column index of the provided UTF-16 index.
+  pub fn line_and_column_index(&self, utf16_index: usize) -> LineAndColumnIndex {
+    self.line_and_column_index_with_encoding(utf16_index, ColumnEncoding::Utf32)
+  }
+
+  /// Fallible version of [`line_and_column_index`](Self::line_and_column_index).
+  pub fn try_line_and_column_index(
+    &self,
+    utf16_index: usize,
+  ) -> Result<LineAndColumnIndex, TextLinesError> {
+    self.try_line_and_column_index_with_encoding(utf16_index, ColumnEncoding::Utf32)
+  }
+
+  /// Gets the line and column index of the provided UTF-16 index where the
+  /// column is measured in the provided encoding.
+  pub fn line_and_column_index_with_encoding(
+    &self,
+    utf16_index: usize,
+    encoding: ColumnEncoding,
+  ) -> LineAndColumnIndex {
+    unwrap_or_panic(self.try_line_and_column_index_with_encoding(utf16_index, encoding))
+  }
+
+  /// Fallible version of [`line_and_column_index_with_encoding`](Self::line_and_column_index_with_encoding).
+  pub fn try_line_and_column_index_with_encoding(
+    &self,
+    utf16_index: usize,
+    encoding: ColumnEncoding,
+  ) -> Result<LineAndColumnIndex, TextLinesError> {
+    let byte_index = self.try_byte_index_from_utf16_index(utf16_index)?;
+    self
+      .lines
+      .try_line_and_column_index_with_encoding(byte_index, encoding)
+  }
+
+  /// Gets the line and column display based on the indentation width and
+  /// the provided UTF-16 index.
+  pub fn line_and_column_display(&self, utf16_index: usize) -> LineAndColumnDisplay {
+    unwrap_or_panic(self.try_line_and_column_display(utf16_index))
+  }
+
+  /// Fallible version of [`line_and_column_display`](Self::line_and_column_display).
+  pub fn try_line_and_column_display(
+    &self,
+    utf16_index: usize,
+  ) -> Result<LineAndColumnDisplay, TextLinesError> {
+    let byte_index = self.try_byte_index_from_utf16_index(utf16_index)?;
+    self.lines.try_line_and_column_display(byte_index)
+  }
+
+  /// Gets the byte index in the text transcoded to UTF-8 from the
+  /// provided UTF-16 index.
+  ///
+  /// The byte index of the character is returned for an index in the
+  /// middle of a surrogate pair.
+  pub fn byte_index_from_utf16_index(&self, utf16_index: usize) -> usize {
+    unwrap_or_panic(self.try_byte_index_from_utf16_index(utf16_index))
+  }
+
+  /// Fallible version of [`byte_index_from_utf16_index`](Self::byte_index_from_utf16_index).
+  pub fn try_byte_index_from_utf16_index(
+    &self,
+    utf16_index: usize,
+  ) -> Result<usize, TextLinesError> {
+    let line_index = self.try_line_index(utf16_index)?;
+    let line_start = self.line_utf16_starts.get(line_index);
+    if utf16_index < line_start {
+      return Ok(0); // at the BOM
+    }
+    let line = self.lines.lines.line(line_index);
+    let column_index = utf16_index - line_start;
+    let utf16_measure = ColumnMeasure::Encoding(ColumnEncoding::Utf16);
+    let line_end_column_index = line.column_index(line.end_index, utf16_measure);
+    Ok(if column_index <= line_end_column_index {
+      line.byte_index(column_index, ColumnEncoding::Utf16)
+    } else {
+      // in a \r\n newline since other newlines are a single code unit
+      line.end_index + column_index - line_end_column_index
+    })
+  }
+
+  /// Gets the UTF-16 index from the provided byte index in the text
+  /// transcoded to UTF-8.
+  ///
+  /// The UTF-16 index of the character is returned for a byte index in the
+  /// middle of a character.
+  pub fn utf16_index_from_byte_index(&self, byte_index: usize) -> usize {
+    unwrap_or_panic(self.try_utf16_index_from_byte_index(byte_index))
+  }
+
+  /// Fallible version of [`utf16_index_from_byte_index`](Self::utf16_index_from_byte_index).
+  pub fn try_utf16_index_from_byte_index(
+    &self,
+    byte_index: usize,
+  ) -> Result<usize, TextLinesError> {
+    let line_index = self.lines.try_line_index(byte_index)?;
+    Ok(self.utf16_index_in_line(line_index, byte_index))
+  }
+
+  /// Gets the UTF-16 index of a byte index within the line or its newline.
+  fn utf16_index_in_line(&self, line_index: usize, byte_index: usize) -> usize {
+    let line = self.lines.lines.line(line_index);
+    if byte_index < line.start_index {
+      return 0; // at the BOM
+    }
+    self.line_utf16_starts.get(line_index)
+      + line.column_index(byte_index, ColumnMeasure::Encoding(ColumnEncoding::Utf16))
+  }
+
+  fn validate_utf16_index(&self, utf16_index: usize) -> Result<(), TextLinesError> {
+    if utf16_index > self.text_length {
+      Err(TextLinesError::Utf16IndexOutOfRange {
+        utf16_index,
+        text_length: self.text_length,
+      })
+    } else {
+      Ok(())
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_helpers::for_each_oracle;
+
+  #[test]
+  fn matches_text_lines() {
+    for_each_oracle(|text, options, lines| {
+      let utf16_text = text.encode_utf16().collect::<Vec<_>>();
+      let utf16_lines = Utf16TextLines::with_options(&utf16_text, options);
+      assert_eq!(utf16_lines.text_lines(), lines);
+      assert_eq!(utf16_lines.text_length(), utf16_text.len());
+      assert_eq!(utf16_lines.lines_count(), lines.lines_count());
+
+      let to_utf16_index = |byte_index: usize| text[..byte_index].encode_utf16().count();
+      for line_index in 0..lines.lines_count() {
+        let (start, end) = lines.line_range(line_index);
+        assert_eq!(
+          utf16_lines.line_range(line_index),
+          (to_utf16_index(start), to_utf16_index(end)),
+          "Text: {:?}, Line index: {}",
+          text,
+          line_index
+        );
+        let line_and_column = LineAndColumnIndex {
+          line_index,
+          column_index: 1,
+        };
+        assert_eq!(
+          utf16_lines.utf16_index(line_and_column),
+          to_utf16_index(lines.byte_index(line_and_column))
+        );
+      }
+
+      let byte_indexes = text
+        .char_indices()
+        .map(|(index, _)| index)
+        .chain(std::iter::once(text.len()));
+      for byte_index in byte_indexes {
+        let utf16_index = to_utf16_index(byte_index);
+        let message = format!("Text: {:?}, Byte index: {}", text, byte_index);
+        assert_eq!(
+          utf16_lines.byte_index_from_utf16_index(utf16_index),
+          byte_index,
+          "{}",
+          message
+        );
+        assert_eq!(
+          utf16_lines.utf16_index_from_byte_index(byte_index),
+          utf16_index,
+          "{}",
+          message
+        );
+        assert_eq!(
+          utf16_lines.line_index(utf16_index),
+          lines.line_index(byte_index),
+          "{}",
+          message
+        );
+        assert_eq!(
+          utf16_lines.line_and_column_index_with_encoding(utf16_index, ColumnEncoding::Graphemes),
+          lines.line_and_column_index_with_encoding(byte_index, ColumnEncoding::Graphemes),
+          "{}",
+          message
+        );
+        assert_eq!(
+          utf16_lines.line_and_column_display(utf16_index),
+          lines.line_and_column_display(byte_index),
+          "{}",
+          message
+        );
+      }
+    });
+  }
+
+  #[test]
+  fn unpaired_surrogates() {
+    // a, unpaired high surrogate, \n, 😀, unpaired low surrogate
+    let utf16_text = [0x61, 0xD83D, 0x0A, 0xD83D, 0xDE00, 0xDE00];
+    let utf16_lines = Utf16TextLines::new(&utf16_text);
+    assert_eq!(
+      utf16_lines.text_lines(),
+      &TextLines::new("a\u{FFFD}\n😀\u{FFFD}")
+    );
+    assert_eq!(utf16_lines.line_range(0), (0, 2));
+    assert_eq!(utf16_lines.line_range(1), (3, 6));
+    assert_eq!(utf16_lines.byte_index_from_utf16_index(2), 4);
+    // in the middle of the surrogate pair
+    assert_eq!(utf16_lines.byte_index_from_utf16_index(4), 5);
+    assert_eq!(utf16_lines.byte_index_from_utf16_index(5), 9);
+    assert_eq!(utf16_lines.utf16_index_from_byte_index(12), 6);
+    assert_eq!(
+      utf16_lines.line_and_column_index(5),
+      LineAndColumnIndex {
+        line_index: 1,
+        column_index: 1,
+      }
+    );
+  }
+
+  #[test]
+  fn errors() {
+    let utf16_lines = Utf16TextLines::new(&[0x31, 0x0A, 0x32]);
+    assert_eq!(
+      utf16_lines.try_line_index(4),
+      Err(TextLinesError::Utf16IndexOutOfRange {
+        utf16_index: 4,
+        text_length: 3,
+      })
+    );
+    assert_eq!(
+      utf16_lines.try_line_range(2),
+      Err(TextLinesError::LineIndexOutOfRange {
+        line_index: 2,
+        lines_count: 2,
+      })
+    );
+    assert_eq!(
+      utf16_lines.try_utf16_index_from_byte_index(4),
+      Err(TextLinesError::ByteIndexOutOfRange {
+        byte_index: 4,
+        text_length: 3,
+      })
+    );
+  }
+}